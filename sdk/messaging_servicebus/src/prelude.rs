@@ -1 +1,4 @@
-pub use crate::service_bus::{QueueClient, SubscriptionReceiver, TopicClient, TopicSender};
+pub use crate::service_bus::{
+    QueueClient, ServiceBusConnectionStringProperties, ServiceBusError, ServiceBusMessage,
+    ServiceBusMessageBatch, ServiceBusMessageState, SubscriptionReceiver, TopicClient, TopicSender,
+};
@@ -0,0 +1,403 @@
+use azure_core::error::{Error, ErrorKind};
+use url::Url;
+
+use super::ServiceBusError;
+
+// Key names.
+const ENDPOINT_KEY_NAME: &str = "Endpoint";
+const ENTITY_PATH_KEY_NAME: &str = "EntityPath";
+const SHARED_ACCESS_KEY_NAME_KEY_NAME: &str = "SharedAccessKeyName";
+const SHARED_ACCESS_KEY_KEY_NAME: &str = "SharedAccessKey";
+const SHARED_ACCESS_SIGNATURE_KEY_NAME: &str = "SharedAccessSignature";
+
+/// Domain suffix of namespaces in the public Azure cloud
+const NAMESPACE_DOMAIN_SUFFIX: &str = ".servicebus.windows.net";
+
+/// The properties of a Service Bus connection string.
+///
+/// A connection string has the form
+/// `Endpoint=sb://<namespace>.servicebus.windows.net/;SharedAccessKeyName=<policy>;SharedAccessKey=<key>`,
+/// optionally followed by `;EntityPath=<queue or topic>` when it is scoped to a single entity.
+/// A `SharedAccessSignature=<token>` may be given instead of the key name and key.
+///
+/// Key names are matched case-insensitively and unknown keys are ignored, as in the
+/// other Azure SDKs. The `Debug` output redacts the shared access key and signature.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ServiceBusConnectionStringProperties {
+    endpoint: Url,
+    fully_qualified_namespace: String,
+    entity_path: Option<String>,
+    shared_access_key_name: Option<String>,
+    shared_access_key: Option<String>,
+    shared_access_signature: Option<String>,
+}
+
+impl ServiceBusConnectionStringProperties {
+    /// Parses and validates a Service Bus connection string
+    ///
+    /// An error is returned if the connection string is malformed, has no
+    /// `Endpoint`, or does not specify exactly one means of authentication
+    /// (a key name and key, or a shared access signature).
+    pub fn parse(connection_string: &str) -> azure_core::Result<Self> {
+        let mut endpoint = None;
+        let mut entity_path = None;
+        let mut shared_access_key_name = None;
+        let mut shared_access_key = None;
+        let mut shared_access_signature = None;
+
+        let kv_str_pairs = connection_string
+            .split(';')
+            .filter(|s| !s.chars().all(char::is_whitespace));
+
+        for kv_pair_str in kv_str_pairs {
+            let (k, v) = match kv_pair_str.trim().split_once('=') {
+                Some((k, _)) if k.trim().is_empty() => {
+                    return Err(invalid_connection_string("no key found".to_owned()))
+                }
+                Some((k, v)) if v.trim().is_empty() => {
+                    return Err(invalid_connection_string(format!(
+                        "missing value for key: {}",
+                        k.trim()
+                    )))
+                }
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => return Err(invalid_connection_string("no key/value found".to_owned())),
+            };
+
+            if k.eq_ignore_ascii_case(ENDPOINT_KEY_NAME) {
+                endpoint = Some(v);
+            } else if k.eq_ignore_ascii_case(ENTITY_PATH_KEY_NAME) {
+                entity_path = Some(v.to_owned());
+            } else if k.eq_ignore_ascii_case(SHARED_ACCESS_KEY_NAME_KEY_NAME) {
+                shared_access_key_name = Some(v.to_owned());
+            } else if k.eq_ignore_ascii_case(SHARED_ACCESS_KEY_KEY_NAME) {
+                shared_access_key = Some(v.to_owned());
+            } else if k.eq_ignore_ascii_case(SHARED_ACCESS_SIGNATURE_KEY_NAME) {
+                shared_access_signature = Some(v.to_owned());
+            }
+        }
+
+        let endpoint = endpoint
+            .ok_or_else(|| invalid_connection_string(format!("missing the {ENDPOINT_KEY_NAME}")))?;
+        let endpoint = Url::parse(endpoint).map_err(|error| {
+            invalid_connection_string(format!(
+                "{ENDPOINT_KEY_NAME} is not a valid URL ({error}): {endpoint}"
+            ))
+        })?;
+        let fully_qualified_namespace = endpoint
+            .host_str()
+            .ok_or_else(|| {
+                invalid_connection_string(format!("{ENDPOINT_KEY_NAME} has no host: {endpoint}"))
+            })?
+            .to_owned();
+
+        match (
+            &shared_access_key_name,
+            &shared_access_key,
+            &shared_access_signature,
+        ) {
+            (Some(_), Some(_), None) | (None, None, Some(_)) => {}
+            (Some(_), None, None) | (None, Some(_), None) => {
+                return Err(invalid_connection_string(format!("must specify both the {SHARED_ACCESS_KEY_NAME_KEY_NAME} and the {SHARED_ACCESS_KEY_KEY_NAME}")))
+            }
+            (None, None, None) => {
+                return Err(invalid_connection_string(format!("must specify either a {SHARED_ACCESS_KEY_NAME_KEY_NAME} and {SHARED_ACCESS_KEY_KEY_NAME}, or a {SHARED_ACCESS_SIGNATURE_KEY_NAME}")))
+            }
+            (_, _, Some(_)) => {
+                return Err(invalid_connection_string(format!("cannot specify both a {SHARED_ACCESS_SIGNATURE_KEY_NAME} and a {SHARED_ACCESS_KEY_KEY_NAME}")))
+            }
+        }
+
+        Ok(Self {
+            endpoint,
+            fully_qualified_namespace,
+            entity_path,
+            shared_access_key_name,
+            shared_access_key,
+            shared_access_signature,
+        })
+    }
+
+    /// The endpoint of the namespace, e.g. `sb://<namespace>.servicebus.windows.net/`
+    pub fn endpoint(&self) -> &Url {
+        &self.endpoint
+    }
+
+    /// The fully qualified namespace, e.g. `<namespace>.servicebus.windows.net`
+    pub fn fully_qualified_namespace(&self) -> &str {
+        &self.fully_qualified_namespace
+    }
+
+    /// The queue or topic the connection string is scoped to, if any
+    pub fn entity_path(&self) -> Option<&str> {
+        self.entity_path.as_deref()
+    }
+
+    /// The name of the shared access policy, if the connection string uses a key
+    pub fn shared_access_key_name(&self) -> Option<&str> {
+        self.shared_access_key_name.as_deref()
+    }
+
+    /// The shared access key, if the connection string uses a key
+    pub fn shared_access_key(&self) -> Option<&str> {
+        self.shared_access_key.as_deref()
+    }
+
+    /// The pre-computed shared access signature, if the connection string uses one
+    pub fn shared_access_signature(&self) -> Option<&str> {
+        self.shared_access_signature.as_deref()
+    }
+
+    /// Resolves what a client needs to connect to a single queue or topic
+    ///
+    /// The entity is taken from `entity_path` or from the `EntityPath` of the
+    /// connection string. It is an error if neither supplies one, or if both do
+    /// and they differ.
+    pub(crate) fn client_credentials<'a>(
+        &'a self,
+        entity_path: Option<&'a str>,
+    ) -> azure_core::Result<ClientCredentials<'a>> {
+        let entity_path = match (entity_path, self.entity_path.as_deref()) {
+            (Some(entity_path), Some(embedded)) if entity_path != embedded => {
+                return Err(invalid_connection_string(format!("{ENTITY_PATH_KEY_NAME} {embedded} does not match the entity path: {entity_path}")))
+            }
+            (Some(entity_path), _) | (None, Some(entity_path)) => entity_path,
+            (None, None) => {
+                return Err(invalid_connection_string(format!("no {ENTITY_PATH_KEY_NAME}, and no entity path was given")))
+            }
+        };
+
+        let namespace = self
+            .fully_qualified_namespace
+            .strip_suffix(NAMESPACE_DOMAIN_SUFFIX)
+            .ok_or_else(|| {
+                invalid_connection_string(format!(
+                    "{ENDPOINT_KEY_NAME} is not a {NAMESPACE_DOMAIN_SUFFIX} namespace: {}",
+                    self.endpoint
+                ))
+            })?;
+
+        let (policy_name, policy_key) = self
+            .shared_access_key_name
+            .as_deref()
+            .zip(self.shared_access_key.as_deref())
+            .ok_or_else(|| {
+                invalid_connection_string(format!("must specify a {SHARED_ACCESS_KEY_NAME_KEY_NAME} and {SHARED_ACCESS_KEY_KEY_NAME} to create a client"))
+            })?;
+
+        Ok(ClientCredentials {
+            namespace,
+            entity_path,
+            policy_name,
+            policy_key,
+        })
+    }
+}
+
+impl std::fmt::Debug for ServiceBusConnectionStringProperties {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redacted = |secret: &Option<String>| secret.as_ref().map(|_| "<REDACTED>");
+
+        f.debug_struct("ServiceBusConnectionStringProperties")
+            .field("endpoint", &self.endpoint)
+            .field("fully_qualified_namespace", &self.fully_qualified_namespace)
+            .field("entity_path", &self.entity_path)
+            .field("shared_access_key_name", &self.shared_access_key_name)
+            .field("shared_access_key", &redacted(&self.shared_access_key))
+            .field(
+                "shared_access_signature",
+                &redacted(&self.shared_access_signature),
+            )
+            .finish()
+    }
+}
+
+fn invalid_connection_string(reason: String) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        ServiceBusError::InvalidConnectionString { reason },
+    )
+}
+
+/// The parts of a connection string used to create a client
+pub(crate) struct ClientCredentials<'a> {
+    pub(crate) namespace: &'a str,
+    pub(crate) entity_path: &'a str,
+    pub(crate) policy_name: &'a str,
+    pub(crate) policy_key: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_key_connection_string() {
+        let properties = ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=RootManageSharedAccessKey;SharedAccessKey=a2V5",
+        )
+        .unwrap();
+
+        assert_eq!(
+            properties.endpoint().as_str(),
+            "sb://namespace.servicebus.windows.net/"
+        );
+        assert_eq!(
+            properties.fully_qualified_namespace(),
+            "namespace.servicebus.windows.net"
+        );
+        assert_eq!(properties.entity_path(), None);
+        assert_eq!(
+            properties.shared_access_key_name(),
+            Some("RootManageSharedAccessKey")
+        );
+        assert_eq!(properties.shared_access_key(), Some("a2V5"));
+        assert_eq!(properties.shared_access_signature(), None);
+    }
+
+    #[test]
+    fn it_parses_entity_path() {
+        let properties = ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=a2V5;EntityPath=myqueue",
+        )
+        .unwrap();
+
+        assert_eq!(properties.entity_path(), Some("myqueue"));
+    }
+
+    #[test]
+    fn it_parses_shared_access_signature() {
+        let properties = ServiceBusConnectionStringProperties::parse(
+            "endpoint = sb://namespace.servicebus.windows.net/ ; sharedaccesssignature = SharedAccessSignature sr=sb%3a%2f%2fnamespace.servicebus.windows.net%2f&sig=c2ln&se=1700000000&skn=send ;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            properties.shared_access_signature(),
+            Some("SharedAccessSignature sr=sb%3a%2f%2fnamespace.servicebus.windows.net%2f&sig=c2ln&se=1700000000&skn=send")
+        );
+        assert_eq!(properties.shared_access_key_name(), None);
+    }
+
+    #[test]
+    fn debug_output_redacts_secrets() {
+        let properties = ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=SECRETKEY",
+        )
+        .unwrap();
+        let debug = format!("{properties:?}");
+        assert!(!debug.contains("SECRETKEY"));
+        assert!(debug.contains("send"));
+
+        let properties = ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessSignature=SharedAccessSignature sig=SECRETSIG",
+        )
+        .unwrap();
+        assert!(!format!("{properties:?}").contains("SECRETSIG"));
+    }
+
+    #[test]
+    fn it_ignores_unknown_keys() {
+        assert!(ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=a2V5;UseDevelopmentEmulator=false",
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn it_resolves_client_credentials() {
+        let properties = ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=a2V5;EntityPath=myqueue",
+        )
+        .unwrap();
+
+        let credentials = properties.client_credentials(None).unwrap();
+        assert_eq!(credentials.namespace, "namespace");
+        assert_eq!(credentials.entity_path, "myqueue");
+        assert_eq!(credentials.policy_name, "send");
+        assert_eq!(credentials.policy_key, "a2V5");
+
+        assert_eq!(
+            properties
+                .client_credentials(Some("myqueue"))
+                .unwrap()
+                .entity_path,
+            "myqueue"
+        );
+        assert!(properties.client_credentials(Some("otherqueue")).is_err());
+    }
+
+    #[test]
+    fn it_requires_an_entity_path_for_clients() {
+        let properties = ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=a2V5",
+        )
+        .unwrap();
+
+        assert!(properties.client_credentials(None).is_err());
+        assert_eq!(
+            properties
+                .client_credentials(Some("myqueue"))
+                .unwrap()
+                .entity_path,
+            "myqueue"
+        );
+    }
+
+    #[test]
+    fn it_creates_client_from_entity_path() {
+        let client = crate::prelude::QueueClient::from_connection_string(
+            azure_core::new_http_client(),
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=a2V5;EntityPath=myqueue",
+            None,
+        );
+
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn errors_carry_the_reason() {
+        let error = ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send",
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::InvalidConnectionString {
+                reason: "must specify both the SharedAccessKeyName and the SharedAccessKey"
+                    .to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn it_returns_expected_errors() {
+        // malformed pairs
+        assert!(ServiceBusConnectionStringProperties::parse("Endpoint=").is_err());
+        assert!(ServiceBusConnectionStringProperties::parse("MissingEquals").is_err());
+        assert!(ServiceBusConnectionStringProperties::parse("=value").is_err());
+        // missing or invalid endpoint
+        assert!(ServiceBusConnectionStringProperties::parse(
+            "SharedAccessKeyName=send;SharedAccessKey=a2V5"
+        )
+        .is_err());
+        assert!(ServiceBusConnectionStringProperties::parse(
+            "Endpoint=namespace;SharedAccessKeyName=send;SharedAccessKey=a2V5"
+        )
+        .is_err());
+        // missing or conflicting credentials
+        assert!(ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/"
+        )
+        .is_err());
+        assert!(ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send"
+        )
+        .is_err());
+        assert!(ServiceBusConnectionStringProperties::parse(
+            "Endpoint=sb://namespace.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=a2V5;SharedAccessSignature=sig"
+        )
+        .is_err());
+    }
+}
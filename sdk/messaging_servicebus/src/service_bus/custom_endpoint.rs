@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use azure_core::{
+    error::{Error, ErrorKind},
+    HttpClient, Request, Response,
+};
+use url::Url;
+
+/// An [`HttpClient`] that sends requests for a namespace to a custom endpoint
+///
+/// Requests are still built and signed for `<namespace>.servicebus.windows.net`,
+/// so the shared access signature targets the namespace as the service expects,
+/// and only the scheme, host and port are replaced right before sending.
+/// Requests for any other host are sent unchanged.
+#[derive(Debug)]
+pub(crate) struct CustomEndpointHttpClient {
+    http_client: Arc<dyn HttpClient>,
+    namespace_host: String,
+    custom_endpoint: Url,
+}
+
+impl CustomEndpointHttpClient {
+    pub(crate) fn new(
+        http_client: Arc<dyn HttpClient>,
+        namespace: &str,
+        custom_endpoint: Url,
+    ) -> azure_core::Result<Self> {
+        if custom_endpoint.host_str().is_none() {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("custom endpoint has no host: {custom_endpoint}")
+            }));
+        }
+
+        Ok(Self {
+            http_client,
+            namespace_host: format!("{namespace}.servicebus.windows.net"),
+            custom_endpoint,
+        })
+    }
+
+    /// Replaces the namespace host of `url` with the custom endpoint
+    fn redirect(&self, url: &mut Url) -> azure_core::Result<()> {
+        if url.host_str() != Some(self.namespace_host.as_str()) {
+            return Ok(());
+        }
+
+        let redirect_error = || {
+            Error::with_message(ErrorKind::Other, || {
+                format!(
+                    "failed to redirect request to custom endpoint {}",
+                    self.custom_endpoint
+                )
+            })
+        };
+        url.set_scheme(self.custom_endpoint.scheme())
+            .map_err(|_| redirect_error())?;
+        url.set_host(self.custom_endpoint.host_str())
+            .map_err(|_| redirect_error())?;
+        url.set_port(self.custom_endpoint.port())
+            .map_err(|_| redirect_error())?;
+        Ok(())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpClient for CustomEndpointHttpClient {
+    async fn execute_request(&self, request: &Request) -> azure_core::Result<Response> {
+        let mut request = request.clone();
+        self.redirect(request.url_mut())?;
+        self.http_client.execute_request(&request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(custom_endpoint: &str) -> CustomEndpointHttpClient {
+        CustomEndpointHttpClient::new(
+            azure_core::new_http_client(),
+            "namespace",
+            Url::parse(custom_endpoint).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_redirects_namespace_requests() {
+        let client = client("https://servicebus.contoso.internal:8443");
+        let mut url =
+            Url::parse("https://namespace.servicebus.windows.net/queue/messages/head?timeout=5")
+                .unwrap();
+
+        client.redirect(&mut url).unwrap();
+
+        assert_eq!(
+            url.as_str(),
+            "https://servicebus.contoso.internal:8443/queue/messages/head?timeout=5"
+        );
+    }
+
+    #[test]
+    fn it_leaves_other_hosts_unchanged() {
+        let client = client("https://servicebus.contoso.internal");
+        let mut url = Url::parse("https://other.servicebus.windows.net/queue/messages").unwrap();
+
+        client.redirect(&mut url).unwrap();
+
+        assert_eq!(
+            url.as_str(),
+            "https://other.servicebus.windows.net/queue/messages"
+        );
+    }
+}
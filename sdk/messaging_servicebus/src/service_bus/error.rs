@@ -0,0 +1,331 @@
+use azure_core::{
+    error::{Error, ErrorKind},
+    headers::{self, Headers},
+    CollectedResponse, HttpClient, Request, StatusCode,
+};
+use std::{fmt, time::Duration};
+
+/// An error condition reported by Service Bus
+///
+/// These are wrapped in the [`azure_core::Error`] returned by the clients, and
+/// can be matched on with [`azure_core::Error::downcast_ref`]:
+///
+/// ```no_run
+/// # async fn settle(message: azure_messaging_servicebus::service_bus::PeekLockResponse) {
+/// use azure_messaging_servicebus::service_bus::ServiceBusError;
+///
+/// if let Err(error) = message.delete_message().await {
+///     match error.downcast_ref::<ServiceBusError>() {
+///         Some(ServiceBusError::MessageLockExpired { .. }) => { /* receive the message again */ }
+///         _ => { /* ... */ }
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ServiceBusError {
+    /// The request was not authorized, e.g. the policy lacks the needed claim
+    /// or the shared access signature is invalid
+    Unauthorized,
+    /// The queue, topic or subscription does not exist
+    MessagingEntityNotFound,
+    /// The entity is full or the namespace quota was exceeded
+    QuotaExceeded,
+    /// The message or batch is larger than the entity accepts
+    MessageSizeExceeded,
+    /// The service is busy and the request should be retried later
+    ServerBusy {
+        /// How long the service asked to wait before retrying, from the
+        /// `Retry-After` header of the response
+        retry_after: Option<Duration>,
+    },
+    /// The service did not complete the operation in time
+    ServiceTimeout,
+    /// A message property is longer than Service Bus accepts
+    ValueTooLong {
+        /// The name of the property, e.g. `MessageId`
+        field: &'static str,
+        /// The maximum length of the property in UTF-16 code units
+        max: usize,
+    },
+    /// A partition key of a message differs from its session identifier
+    ///
+    /// Messages with a session are partitioned by their session identifier, so
+    /// their partition keys must be equal to it.
+    PartitionKeyMismatch {
+        /// The name of the property, e.g. `PartitionKey`
+        field: &'static str,
+        /// The partition key that was given
+        partition_key: String,
+        /// The session identifier of the message
+        session_id: String,
+    },
+    /// An application property cannot be sent as a message header
+    InvalidApplicationProperty {
+        /// The name of the property
+        name: String,
+        /// Why the property cannot be sent
+        reason: &'static str,
+    },
+    /// The connection string is malformed or lacks what the client needs
+    InvalidConnectionString {
+        /// What is wrong with the connection string
+        reason: String,
+    },
+    /// The lock on the message expired, or the message was already settled,
+    /// so it can no longer be settled or have its lock renewed
+    ///
+    /// This typically happens when processing a message takes longer than the
+    /// lock duration of the entity; renewing the lock with
+    /// [`PeekLockResponse::renew_message_lock`](crate::service_bus::PeekLockResponse::renew_message_lock)
+    /// while processing avoids it.
+    MessageLockExpired {
+        /// The identifier of the message whose lock was lost
+        message_id: Option<String>,
+        /// The sequence number of the message whose lock was lost
+        sequence_number: Option<i64>,
+    },
+}
+
+impl ServiceBusError {
+    /// Whether the operation may succeed if retried after a while
+    ///
+    /// Busy and timed out requests are transient; every other condition needs
+    /// the request, the message or the entity to be changed first.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::ServerBusy { .. } | Self::ServiceTimeout)
+    }
+
+    /// The error condition the service signals with an error response, if any
+    fn from_response(status: StatusCode, headers: &Headers) -> Option<Self> {
+        match status {
+            StatusCode::Unauthorized => Some(Self::Unauthorized),
+            StatusCode::NotFound | StatusCode::Gone => Some(Self::MessagingEntityNotFound),
+            StatusCode::Forbidden => Some(Self::QuotaExceeded),
+            StatusCode::PayloadTooLarge => Some(Self::MessageSizeExceeded),
+            StatusCode::ServiceUnavailable | StatusCode::TooManyRequests => {
+                Some(Self::ServerBusy {
+                    retry_after: headers
+                        .get_optional_str(&headers::RETRY_AFTER)
+                        .and_then(|seconds| seconds.trim().parse().ok())
+                        .map(Duration::from_secs),
+                })
+            }
+            StatusCode::RequestTimeout | StatusCode::GatewayTimeout => Some(Self::ServiceTimeout),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for ServiceBusError {}
+
+impl fmt::Display for ServiceBusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "the request was not authorized"),
+            Self::MessagingEntityNotFound => {
+                write!(f, "the queue, topic or subscription was not found")
+            }
+            Self::QuotaExceeded => write!(f, "the quota of the entity or namespace was exceeded"),
+            Self::MessageSizeExceeded => write!(f, "the message is too large"),
+            Self::ServerBusy {
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "the service is busy, retry after {} seconds",
+                retry_after.as_secs()
+            ),
+            Self::ServerBusy { retry_after: None } => write!(f, "the service is busy"),
+            Self::ServiceTimeout => write!(f, "the service timed out"),
+            Self::ValueTooLong { field, max } => {
+                write!(f, "{field} is longer than {max} UTF-16 code units")
+            }
+            Self::PartitionKeyMismatch {
+                field,
+                partition_key,
+                session_id,
+            } => write!(
+                f,
+                "{field} \"{partition_key}\" does not match the session id \"{session_id}\""
+            ),
+            Self::InvalidApplicationProperty { name, reason } => {
+                write!(f, "invalid application property \"{name}\": {reason}")
+            }
+            Self::InvalidConnectionString { reason } => {
+                write!(f, "invalid connection string: {reason}")
+            }
+            Self::MessageLockExpired {
+                message_id,
+                sequence_number,
+            } => {
+                write!(f, "the lock on the message")?;
+                if let Some(message_id) = message_id {
+                    write!(f, " \"{message_id}\"")?;
+                }
+                if let Some(sequence_number) = sequence_number {
+                    write!(f, " (sequence number {sequence_number})")?;
+                }
+                write!(f, " has expired or was lost")
+            }
+        }
+    }
+}
+
+/// Sends the request and collects the response
+///
+/// Like [`HttpClient::execute_request_check_status`], an error is returned if
+/// the status is not success, but it also carries the [`ServiceBusError`] the
+/// response signals.
+pub(crate) async fn execute_request_check_status(
+    http_client: &dyn HttpClient,
+    request: &Request,
+) -> azure_core::Result<CollectedResponse> {
+    let (status, headers, body) = http_client.execute_request(request).await?.deconstruct();
+    let body = body.collect().await?;
+
+    if status.is_success() {
+        Ok(CollectedResponse::new(status, headers, body))
+    } else {
+        Err(status_error(status, &headers, &body))
+    }
+}
+
+/// The error for a response with an unsuccessful or unexpected status
+pub(crate) fn status_error(status: StatusCode, headers: &Headers, body: &[u8]) -> Error {
+    let kind = ErrorKind::http_response_from_body(status, body);
+    match ServiceBusError::from_response(status, headers) {
+        Some(service_bus_error) => Error::new(kind, service_bus_error),
+        None => kind.into_error(),
+    }
+}
+
+/// Maps the error of an operation on a locked message
+///
+/// The service answers `404 Not Found` when the lock is no longer held.
+pub(crate) fn map_lock_error(
+    error: Error,
+    message_id: Option<&str>,
+    sequence_number: Option<i64>,
+) -> Error {
+    match error.kind() {
+        ErrorKind::HttpResponse {
+            status: StatusCode::NotFound,
+            ..
+        } => Error::new(
+            error.kind().clone(),
+            ServiceBusError::MessageLockExpired {
+                message_id: message_id.map(ToOwned::to_owned),
+                sequence_number,
+            },
+        ),
+        _ => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_found_maps_to_lock_expired() {
+        let error = map_lock_error(
+            ErrorKind::http_response(StatusCode::NotFound, None).into_error(),
+            Some("order-42"),
+            Some(7),
+        );
+
+        let service_bus_error = error.downcast_ref::<ServiceBusError>().unwrap();
+        assert_eq!(
+            service_bus_error,
+            &ServiceBusError::MessageLockExpired {
+                message_id: Some("order-42".into()),
+                sequence_number: Some(7),
+            }
+        );
+        assert_eq!(
+            service_bus_error.to_string(),
+            r#"the lock on the message "order-42" (sequence number 7) has expired or was lost"#
+        );
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::HttpResponse {
+                status: StatusCode::NotFound,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn status_maps_to_service_bus_error() {
+        for (status, expected) in [
+            (StatusCode::Unauthorized, ServiceBusError::Unauthorized),
+            (
+                StatusCode::NotFound,
+                ServiceBusError::MessagingEntityNotFound,
+            ),
+            (StatusCode::Gone, ServiceBusError::MessagingEntityNotFound),
+            (StatusCode::Forbidden, ServiceBusError::QuotaExceeded),
+            (
+                StatusCode::PayloadTooLarge,
+                ServiceBusError::MessageSizeExceeded,
+            ),
+            (
+                StatusCode::ServiceUnavailable,
+                ServiceBusError::ServerBusy { retry_after: None },
+            ),
+            (StatusCode::RequestTimeout, ServiceBusError::ServiceTimeout),
+        ] {
+            let error = status_error(status, &Headers::new(), &[]);
+            assert_eq!(error.downcast_ref::<ServiceBusError>(), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn server_busy_carries_retry_after() {
+        let mut headers = Headers::new();
+        headers.insert(headers::RETRY_AFTER, "10");
+
+        let error = status_error(StatusCode::ServiceUnavailable, &headers, &[]);
+
+        assert_eq!(
+            error.downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::ServerBusy {
+                retry_after: Some(Duration::from_secs(10))
+            })
+        );
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::HttpResponse {
+                status: StatusCode::ServiceUnavailable,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn only_busy_and_timeout_are_transient() {
+        assert!(ServiceBusError::ServerBusy { retry_after: None }.is_transient());
+        assert!(ServiceBusError::ServiceTimeout.is_transient());
+        assert!(!ServiceBusError::Unauthorized.is_transient());
+        assert!(!ServiceBusError::MessagingEntityNotFound.is_transient());
+        assert!(!ServiceBusError::MessageLockExpired {
+            message_id: None,
+            sequence_number: None
+        }
+        .is_transient());
+    }
+
+    #[test]
+    fn other_errors_are_unchanged() {
+        let error = map_lock_error(
+            ErrorKind::http_response(StatusCode::InternalServerError, None).into_error(),
+            None,
+            None,
+        );
+        assert!(error.downcast_ref::<ServiceBusError>().is_none());
+
+        let error = status_error(StatusCode::InternalServerError, &Headers::new(), &[]);
+        assert!(error.downcast_ref::<ServiceBusError>().is_none());
+    }
+}
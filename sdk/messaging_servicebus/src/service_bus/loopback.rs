@@ -0,0 +1,342 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use azure_core::{
+    headers::{self, Headers},
+    Body, HttpClient, Method, Request, Response, StatusCode,
+};
+use bytes::Bytes;
+use serde_json::{Map, Value};
+use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+
+use super::{message::is_application_property, BATCH_CONTENT_TYPE, BROKER_PROPERTIES};
+
+/// How long a peek-locked message stays locked
+const LOCK_DURATION: time::Duration = time::Duration::seconds(30);
+
+/// `TimeToLive` of messages sent without one, as reported by the service
+const DEFAULT_TIME_TO_LIVE: f64 = 922_337_203_685.477_5;
+
+/// An in-memory Service Bus namespace, used to test the clients without a network
+///
+/// It implements the subset of the REST API used by the clients: messages sent
+/// to a queue or topic are kept in order and handed out by the receive, peek-lock
+/// and settlement requests. Like in Service Bus, every subscription of a topic
+/// receives its own copy of each message sent to the topic. Entities need not
+/// be created first: a subscription is created when it is first received from,
+/// with all the messages sent to its topic so far. Requests are not authorized,
+/// so any namespace, policy and key can be given to the clients.
+///
+/// Available with the `test-util` feature, to test code that uses the clients:
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> azure_core::Result<()> {
+/// use azure_messaging_servicebus::service_bus::{LoopbackHttpClient, QueueClient};
+/// use std::sync::Arc;
+///
+/// let loopback = Arc::new(LoopbackHttpClient::default());
+/// let client = QueueClient::new(loopback.clone(), "namespace", "queue", "policy", "a2V5")?;
+///
+/// client.send_message("hello").await?;
+/// assert_eq!(loopback.message_count("queue"), 1);
+/// assert_eq!(client.receive_and_delete_message().await?, "hello");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct LoopbackHttpClient {
+    namespace: Mutex<Namespace>,
+}
+
+#[derive(Debug, Default)]
+struct Namespace {
+    /// Available messages by queue, topic or subscription path
+    entities: HashMap<String, VecDeque<StoredMessage>>,
+    /// Every message sent, by queue or topic, to fill subscriptions created later
+    sent: HashMap<String, Vec<StoredMessage>>,
+    /// Locked messages by the path of their lock, with their entity path
+    locked: HashMap<String, (String, StoredMessage)>,
+    last_sequence_number: i64,
+    last_lock_token: u64,
+    /// Status every request is answered with, if set
+    failure: Option<StatusCode>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredMessage {
+    body: Bytes,
+    content_type: Option<String>,
+    broker_properties: Map<String, Value>,
+    /// Application properties, handed out as headers
+    user_properties: Map<String, Value>,
+}
+
+impl LoopbackHttpClient {
+    /// The number of messages available or locked in the queue or subscription
+    ///
+    /// Subscriptions are given by path, e.g. `topic/subscriptions/subscription`.
+    /// For a topic itself, this is the number of messages sent to it.
+    pub fn message_count(&self, entity_path: &str) -> usize {
+        let namespace = self.namespace.lock().unwrap();
+        let available = match (
+            namespace.entities.get(entity_path),
+            subscription_topic(entity_path),
+        ) {
+            (Some(messages), _) => messages.len(),
+            (None, Some(topic)) => namespace.sent.get(topic).map_or(0, Vec::len),
+            (None, None) => 0,
+        };
+        let locked = namespace
+            .locked
+            .values()
+            .filter(|(entity, _)| entity == entity_path)
+            .count();
+        available + locked
+    }
+
+    /// Answers every following request with `status`, e.g. to simulate an
+    /// authorization failure or a deleted entity
+    pub fn fail_with(&self, status: StatusCode) {
+        self.namespace.lock().unwrap().failure = Some(status);
+    }
+}
+
+impl Namespace {
+    fn handle(&mut self, request: &Request) -> (StatusCode, Headers, Bytes) {
+        if let Some(status) = self.failure {
+            return empty(status);
+        }
+
+        let url = request.url();
+        let segments: Vec<_> = url.path_segments().into_iter().flatten().collect();
+
+        match (request.method(), segments.as_slice()) {
+            (Method::Post, [entity @ .., "messages"]) => self.send(&entity.join("/"), request),
+            (Method::Delete, [entity @ .., "messages", "head"]) => {
+                self.receive(&entity.join("/"), None)
+            }
+            (Method::Post, [entity @ .., "messages", "head"]) => {
+                let entity = entity.join("/");
+                let lock_path = format!("{entity}/messages");
+                self.receive(&entity, Some(lock_path))
+            }
+            (Method::Delete, _) => self.settle(url.path(), false),
+            (Method::Put, _) => self.settle(url.path(), true),
+            (Method::Post, _) => self.renew_lock(url.path()),
+            _ => empty(StatusCode::BadRequest),
+        }
+    }
+
+    fn send(&mut self, entity: &str, request: &Request) -> (StatusCode, Headers, Bytes) {
+        let body = match request.body() {
+            Body::Bytes(body) => body.clone(),
+            _ => return empty(StatusCode::BadRequest),
+        };
+        let content_type = request
+            .headers()
+            .get_optional_string(&headers::CONTENT_TYPE);
+
+        let messages = if content_type.as_deref() == Some(BATCH_CONTENT_TYPE) {
+            let entries: Vec<Map<String, Value>> = match serde_json::from_slice(&body) {
+                Ok(entries) => entries,
+                Err(_) => return empty(StatusCode::BadRequest),
+            };
+            entries
+                .into_iter()
+                .map(|mut entry| StoredMessage {
+                    body: match entry.remove("Body") {
+                        Some(Value::String(body)) => body.into(),
+                        _ => Bytes::new(),
+                    },
+                    content_type: None,
+                    broker_properties: match entry.remove("BrokerProperties") {
+                        Some(Value::Object(broker_properties)) => broker_properties,
+                        _ => Map::new(),
+                    },
+                    user_properties: match entry.remove("UserProperties") {
+                        Some(Value::Object(user_properties)) => user_properties,
+                        _ => Map::new(),
+                    },
+                })
+                .collect()
+        } else {
+            let broker_properties = match request.headers().get_optional_str(&BROKER_PROPERTIES) {
+                Some(json) => match serde_json::from_str(json) {
+                    Ok(broker_properties) => broker_properties,
+                    Err(_) => return empty(StatusCode::BadRequest),
+                },
+                None => Map::new(),
+            };
+            let user_properties = request
+                .headers()
+                .iter()
+                .filter(|(name, _)| is_application_property(name.as_str()))
+                .map(|(name, value)| (name.as_str().to_owned(), value.as_str().into()))
+                .collect();
+            vec![StoredMessage {
+                body,
+                content_type,
+                broker_properties,
+                user_properties,
+            }]
+        };
+
+        let enqueued_time = format_time(OffsetDateTime::now_utc());
+        for mut message in messages {
+            self.last_sequence_number += 1;
+            let properties = &mut message.broker_properties;
+            properties
+                .entry("MessageId")
+                .or_insert_with(|| format!("loopback-{}", self.last_sequence_number).into());
+            properties
+                .entry("TimeToLive")
+                .or_insert_with(|| DEFAULT_TIME_TO_LIVE.into());
+            properties.insert("SequenceNumber".into(), self.last_sequence_number.into());
+            properties.insert("EnqueuedTimeUtc".into(), enqueued_time.clone().into());
+            properties.insert("DeliveryCount".into(), 0.into());
+            properties.insert("State".into(), "Active".into());
+
+            self.sent
+                .entry(entity.to_owned())
+                .or_default()
+                .push(message.clone());
+            for (path, messages) in &mut self.entities {
+                if subscription_topic(path) == Some(entity) {
+                    messages.push_back(message.clone());
+                }
+            }
+            self.entities
+                .entry(entity.to_owned())
+                .or_default()
+                .push_back(message);
+        }
+
+        empty(StatusCode::Created)
+    }
+
+    /// Hands out the next message of `entity`, locking it under `lock_path` if given
+    fn receive(&mut self, entity: &str, lock_path: Option<String>) -> (StatusCode, Headers, Bytes) {
+        if let Some(topic) = subscription_topic(entity) {
+            if !self.entities.contains_key(entity) {
+                let messages = self.sent.get(topic).cloned().unwrap_or_default();
+                self.entities.insert(entity.to_owned(), messages.into());
+            }
+        }
+
+        let Some(mut message) = self.entities.get_mut(entity).and_then(VecDeque::pop_front) else {
+            return empty(StatusCode::NoContent);
+        };
+
+        let properties = &mut message.broker_properties;
+        let delivery_count = properties["DeliveryCount"].as_i64().unwrap_or_default() + 1;
+        properties.insert("DeliveryCount".into(), delivery_count.into());
+
+        let mut headers = Headers::new();
+        if let Some(content_type) = &message.content_type {
+            headers.insert(headers::CONTENT_TYPE, content_type.clone());
+        }
+        for (name, value) in &message.user_properties {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            headers.insert(headers::HeaderName::from(name.clone()), value);
+        }
+
+        let Some(lock_path) = lock_path else {
+            headers.insert(
+                BROKER_PROPERTIES,
+                Value::Object(message.broker_properties.clone()).to_string(),
+            );
+            return (StatusCode::Ok, headers, message.body);
+        };
+
+        self.last_lock_token += 1;
+        let lock_token = format!("00000000-0000-0000-0000-{:012}", self.last_lock_token);
+        let lock_path = format!(
+            "/{lock_path}/{}/{lock_token}",
+            message.broker_properties["SequenceNumber"]
+        );
+        let properties = &mut message.broker_properties;
+        properties.insert("LockToken".into(), lock_token.into());
+        properties.insert(
+            "LockedUntilUtc".into(),
+            format_time(OffsetDateTime::now_utc() + LOCK_DURATION).into(),
+        );
+
+        headers.insert(
+            BROKER_PROPERTIES,
+            Value::Object(message.broker_properties.clone()).to_string(),
+        );
+        headers.insert(
+            headers::LOCATION,
+            format!("https://loopback.servicebus.windows.net{lock_path}"),
+        );
+        let body = message.body.clone();
+        self.locked.insert(lock_path, (entity.to_owned(), message));
+
+        (StatusCode::Created, headers, body)
+    }
+
+    /// Completes the locked message, or abandons it if `abandon` is set
+    fn settle(&mut self, lock_path: &str, abandon: bool) -> (StatusCode, Headers, Bytes) {
+        let Some((entity, mut message)) = self.locked.remove(lock_path) else {
+            return empty(StatusCode::NotFound);
+        };
+
+        if abandon {
+            let properties = &mut message.broker_properties;
+            properties.remove("LockToken");
+            properties.remove("LockedUntilUtc");
+            self.entities.entry(entity).or_default().push_front(message);
+        }
+
+        empty(StatusCode::Ok)
+    }
+
+    fn renew_lock(&mut self, lock_path: &str) -> (StatusCode, Headers, Bytes) {
+        match self.locked.get_mut(lock_path) {
+            Some((_, message)) => {
+                message.broker_properties.insert(
+                    "LockedUntilUtc".into(),
+                    format_time(OffsetDateTime::now_utc() + LOCK_DURATION).into(),
+                );
+                empty(StatusCode::Ok)
+            }
+            None => empty(StatusCode::NotFound),
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpClient for LoopbackHttpClient {
+    async fn execute_request(&self, request: &Request) -> azure_core::Result<Response> {
+        let (status, headers, body) = self.namespace.lock().unwrap().handle(request);
+        Ok(Response::new(
+            status,
+            headers,
+            Box::pin(futures::stream::iter(vec![Ok(body)])),
+        ))
+    }
+}
+
+/// The topic of a subscription path, e.g. `topic/subscriptions/subscription`
+fn subscription_topic(entity_path: &str) -> Option<&str> {
+    match entity_path.split('/').collect::<Vec<_>>().as_slice() {
+        [topic, "subscriptions", _] => Some(topic),
+        _ => None,
+    }
+}
+
+fn empty(status: StatusCode) -> (StatusCode, Headers, Bytes) {
+    (status, Headers::new(), Bytes::new())
+}
+
+fn format_time(time: OffsetDateTime) -> String {
+    time.format(&Rfc2822).unwrap()
+}
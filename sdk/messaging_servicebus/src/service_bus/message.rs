@@ -0,0 +1,981 @@
+use azure_core::{
+    error::{Error, ErrorKind, ResultExt},
+    headers, Request,
+};
+use bytes::Bytes;
+use serde::Serialize;
+use std::{borrow::Cow, collections::BTreeMap, time::Duration};
+
+use super::{fmt_message_summary, PeekLockResponse, ServiceBusError, BROKER_PROPERTIES};
+
+/// Largest message accepted by a Standard tier namespace
+pub(crate) const DEFAULT_MAX_MESSAGE_SIZE_IN_BYTES: usize = 256 * 1024;
+
+/// Longest message id, session id or partition key accepted by Service Bus, in UTF-16 code units
+const MAX_ID_LENGTH: usize = 128;
+
+/// Headers of send requests and received messages that are not application properties
+const RESERVED_HEADER_NAMES: &[&str] = &[
+    "authorization",
+    "brokerproperties",
+    "connection",
+    "content-encoding",
+    "content-length",
+    "content-type",
+    "date",
+    "host",
+    "location",
+    "server",
+    "strict-transport-security",
+    "transfer-encoding",
+];
+
+/// A message to be sent to a queue or topic
+///
+/// The body is sent as-is in the request body. Simple payloads can be
+/// converted directly into a message:
+///
+/// ```
+/// use azure_messaging_servicebus::prelude::*;
+///
+/// let from_str = ServiceBusMessage::from("hello, world!");
+/// let from_string = ServiceBusMessage::from(String::from("hello, world!"));
+/// let from_slice = ServiceBusMessage::from(&b"hello, world!"[..]);
+/// let from_vec = ServiceBusMessage::from(b"hello, world!".to_vec());
+/// let from_bytes = ServiceBusMessage::from(bytes::Bytes::from_static(b"hello, world!"));
+///
+/// assert_eq!(from_str, from_string);
+/// assert_eq!(from_str, from_slice);
+/// assert_eq!(from_str, from_vec);
+/// assert_eq!(from_str, from_bytes);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceBusMessage {
+    body: Bytes,
+    content_type: Option<String>,
+    broker_properties: SendBrokerProperties,
+    /// Custom properties sent as headers, by lowercase name
+    application_properties: BTreeMap<String, String>,
+}
+
+/// Standard message properties sent in the `BrokerProperties` header
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendBrokerProperties {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    partition_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to_session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "SendBrokerProperties::serialize_seconds"
+    )]
+    time_to_live: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    via_partition_key: Option<String>,
+}
+
+impl SendBrokerProperties {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Durations are sent as a number of seconds
+    fn serialize_seconds<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match duration {
+            Some(duration) => serializer.serialize_f64(duration.as_secs_f64()),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+/// A message as serialized in the body of a batch send request
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct BatchEntry<'a> {
+    body: Cow<'a, str>,
+    #[serde(skip_serializing_if = "SendBrokerProperties::is_empty")]
+    broker_properties: &'a SendBrokerProperties,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    user_properties: &'a BTreeMap<String, String>,
+}
+
+impl ServiceBusMessage {
+    /// Creates a new message with the given body
+    pub fn new<B>(body: B) -> Self
+    where
+        B: Into<Bytes>,
+    {
+        Self {
+            body: body.into(),
+            content_type: None,
+            broker_properties: SendBrokerProperties::default(),
+            application_properties: BTreeMap::new(),
+        }
+    }
+
+    /// Get the body of the message
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Get the identifier of the message
+    pub fn message_id(&self) -> Option<&str> {
+        self.broker_properties.message_id.as_deref()
+    }
+
+    /// Set the identifier of the message
+    ///
+    /// If not set, Service Bus assigns a unique identifier when the message is sent.
+    ///
+    /// When duplicate detection is enabled on the queue or topic, any message
+    /// sent with the `MessageId` of a message already sent within the
+    /// duplicate detection history window is accepted but silently dropped,
+    /// so only one copy is ever delivered. Setting a stable identifier makes
+    /// retrying a send idempotent.
+    ///
+    /// Fails if the identifier is longer than 128 UTF-16 code units.
+    pub fn set_message_id<S>(&mut self, message_id: S) -> azure_core::Result<()>
+    where
+        S: Into<String>,
+    {
+        let message_id = message_id.into();
+        check_id_length("MessageId", &message_id)?;
+        self.broker_properties.message_id = Some(message_id);
+        Ok(())
+    }
+
+    /// Get the correlation identifier of the message
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.broker_properties.correlation_id.as_deref()
+    }
+
+    /// Set the correlation identifier of the message
+    ///
+    /// This is typically used in request/reply scenarios to carry the
+    /// `MessageId` of the message being replied to.
+    pub fn set_correlation_id<S>(&mut self, correlation_id: S)
+    where
+        S: Into<String>,
+    {
+        self.broker_properties.correlation_id = Some(correlation_id.into());
+    }
+
+    /// Get the content type of the message body
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Set the content type of the message body, e.g. `application/json`
+    ///
+    /// This is sent as the `Content-Type` of the request and handed back to
+    /// receivers unchanged.
+    pub fn set_content_type<S>(&mut self, content_type: S)
+    where
+        S: Into<String>,
+    {
+        self.content_type = Some(content_type.into());
+    }
+
+    /// Get the application specific subject of the message
+    pub fn subject(&self) -> Option<&str> {
+        self.broker_properties.label.as_deref()
+    }
+
+    /// Set the application specific subject of the message
+    ///
+    /// This is the `Label` broker property, which subscription filters can route on.
+    pub fn set_subject<S>(&mut self, subject: S)
+    where
+        S: Into<String>,
+    {
+        self.broker_properties.label = Some(subject.into());
+    }
+
+    /// Get the address of the entity replies should be sent to
+    pub fn reply_to(&self) -> Option<&str> {
+        self.broker_properties.reply_to.as_deref()
+    }
+
+    /// Set the address of the entity replies should be sent to
+    pub fn set_reply_to<S>(&mut self, reply_to: S)
+    where
+        S: Into<String>,
+    {
+        self.broker_properties.reply_to = Some(reply_to.into());
+    }
+
+    /// Get the session replies should be sent to
+    pub fn reply_to_session_id(&self) -> Option<&str> {
+        self.broker_properties.reply_to_session_id.as_deref()
+    }
+
+    /// Set the session replies should be sent to
+    ///
+    /// This lets the replier target the requester's session on a
+    /// session-enabled [`reply_to`](Self::reply_to) entity.
+    ///
+    /// Fails if the identifier is longer than 128 UTF-16 code units.
+    pub fn set_reply_to_session_id<S>(&mut self, reply_to_session_id: S) -> azure_core::Result<()>
+    where
+        S: Into<String>,
+    {
+        let reply_to_session_id = reply_to_session_id.into();
+        check_id_length("ReplyToSessionId", &reply_to_session_id)?;
+        self.broker_properties.reply_to_session_id = Some(reply_to_session_id);
+        Ok(())
+    }
+
+    /// Get the session identifier of the message
+    pub fn session_id(&self) -> Option<&str> {
+        self.broker_properties.session_id.as_deref()
+    }
+
+    /// Set the session identifier of the message
+    ///
+    /// The session identifier takes precedence over the partition key: if a
+    /// different partition key was already set, it is replaced by the session
+    /// identifier.
+    ///
+    /// Fails if the identifier is longer than 128 UTF-16 code units.
+    pub fn set_session_id<S>(&mut self, session_id: S) -> azure_core::Result<()>
+    where
+        S: Into<String>,
+    {
+        let session_id = session_id.into();
+        check_id_length("SessionId", &session_id)?;
+        for partition_key in [
+            &mut self.broker_properties.partition_key,
+            &mut self.broker_properties.via_partition_key,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if *partition_key != session_id {
+                *partition_key = session_id.clone();
+            }
+        }
+        self.broker_properties.session_id = Some(session_id);
+        Ok(())
+    }
+
+    /// Get the partition key of the message
+    pub fn partition_key(&self) -> Option<&str> {
+        self.broker_properties.partition_key.as_deref()
+    }
+
+    /// Set the partition key used to assign the message to a partition of a
+    /// partitioned entity
+    ///
+    /// Messages sent to a session-enabled entity are partitioned by their
+    /// session identifier, so if a session identifier is set the partition
+    /// key must be equal to it. [`ServiceBusError::PartitionKeyMismatch`] is
+    /// returned otherwise, or [`ServiceBusError::ValueTooLong`] if the key is
+    /// longer than 128 UTF-16 code units, and the message is left unchanged.
+    pub fn set_partition_key<S>(&mut self, partition_key: S) -> azure_core::Result<()>
+    where
+        S: Into<String>,
+    {
+        let partition_key = partition_key.into();
+        check_id_length("PartitionKey", &partition_key)?;
+        if let Some(session_id) = &self.broker_properties.session_id {
+            if *session_id != partition_key {
+                return Err(session_id_mismatch(
+                    "PartitionKey",
+                    &partition_key,
+                    session_id,
+                ));
+            }
+        }
+        self.broker_properties.partition_key = Some(partition_key);
+        Ok(())
+    }
+
+    /// Get the partition key of the transfer queue a transaction routes the message through
+    pub fn via_partition_key(&self) -> Option<&str> {
+        self.broker_properties.via_partition_key.as_deref()
+    }
+
+    /// Set the partition key of the transfer queue a transaction routes the message through
+    ///
+    /// This is needed when a transaction spans entities, so that all of its
+    /// messages go through the same partition. As with the
+    /// [`partition_key`](Self::set_partition_key), if a session identifier is
+    /// set the key must be equal to it, and it may not be longer than 128
+    /// UTF-16 code units. An error is returned otherwise and the message is left unchanged.
+    pub fn set_via_partition_key<S>(&mut self, via_partition_key: S) -> azure_core::Result<()>
+    where
+        S: Into<String>,
+    {
+        let via_partition_key = via_partition_key.into();
+        check_id_length("ViaPartitionKey", &via_partition_key)?;
+        if let Some(session_id) = &self.broker_properties.session_id {
+            if *session_id != via_partition_key {
+                return Err(session_id_mismatch(
+                    "ViaPartitionKey",
+                    &via_partition_key,
+                    session_id,
+                ));
+            }
+        }
+        self.broker_properties.via_partition_key = Some(via_partition_key);
+        Ok(())
+    }
+
+    /// Get the application specific destination address of the message
+    pub fn to(&self) -> Option<&str> {
+        self.broker_properties.to.as_deref()
+    }
+
+    /// Set the application specific destination address of the message
+    ///
+    /// Service Bus does not interpret this value; it is handed to receivers unchanged.
+    pub fn set_to<S>(&mut self, to: S)
+    where
+        S: Into<String>,
+    {
+        self.broker_properties.to = Some(to.into());
+    }
+
+    /// Get the time to live of the message
+    pub fn time_to_live(&self) -> Option<Duration> {
+        self.broker_properties.time_to_live
+    }
+
+    /// Set the time to live of the message
+    ///
+    /// The message expires once it has been in the queue or subscription for
+    /// this long, and is then dropped or dead-lettered depending on the entity.
+    /// The entity's default time to live is used if it is shorter.
+    pub fn set_time_to_live(&mut self, time_to_live: Duration) {
+        self.broker_properties.time_to_live = Some(time_to_live);
+    }
+
+    /// Get the custom properties of the message, by lowercase name
+    pub fn application_properties(&self) -> &BTreeMap<String, String> {
+        &self.application_properties
+    }
+
+    /// Set a custom property of the message
+    ///
+    /// The REST API sends application properties as request headers, so the
+    /// name is case-insensitive and stored in lowercase, and the value is sent
+    /// as-is. Subscription filters can route on these properties.
+    ///
+    /// Fails with [`ServiceBusError::InvalidApplicationProperty`] if the name
+    /// is not a valid header name or is used by the REST API itself, e.g.
+    /// `Content-Type`, or if the value contains control characters.
+    pub fn set_application_property<K, V>(&mut self, name: K, value: V) -> azure_core::Result<()>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let name = name.into().to_ascii_lowercase();
+        let value = value.into();
+        let reason = if !is_header_name(&name) {
+            Some("not a valid header name")
+        } else if !is_application_property(&name) {
+            Some("the header is reserved by Service Bus")
+        } else if value.chars().any(char::is_control) {
+            Some("the value contains control characters")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Err(Error::new(
+                ErrorKind::Other,
+                ServiceBusError::InvalidApplicationProperty { name, reason },
+            ));
+        }
+        self.application_properties.insert(name, value);
+        Ok(())
+    }
+
+    /// Checks the message against the constraints enforced by Service Bus
+    ///
+    /// Messages are validated before every send so that invalid messages fail
+    /// without a round-trip to the service, but this can also be called directly.
+    /// The body is checked against the 256 KB accepted by a Standard tier
+    /// namespace; use [`validate_with_max_size_in_bytes`](Self::validate_with_max_size_in_bytes)
+    /// for the larger messages accepted by Premium tier namespaces.
+    pub fn validate(&self) -> azure_core::Result<()> {
+        self.validate_with_max_size_in_bytes(DEFAULT_MAX_MESSAGE_SIZE_IN_BYTES)
+    }
+
+    /// Checks the message against the constraints enforced by Service Bus,
+    /// allowing a body of up to `max_size_in_bytes`
+    ///
+    /// Fails with [`ServiceBusError::MessageSizeExceeded`] if the body is larger.
+    /// The service also counts the properties of the message towards its size,
+    /// so a body just below the limit may still be rejected when sent.
+    pub fn validate_with_max_size_in_bytes(
+        &self,
+        max_size_in_bytes: usize,
+    ) -> azure_core::Result<()> {
+        if self.body.len() > max_size_in_bytes {
+            return Err(Error::new(
+                ErrorKind::Other,
+                ServiceBusError::MessageSizeExceeded,
+            ));
+        }
+        if let Some(session_id) = &self.broker_properties.session_id {
+            for (name, partition_key) in [
+                ("PartitionKey", &self.broker_properties.partition_key),
+                ("ViaPartitionKey", &self.broker_properties.via_partition_key),
+            ] {
+                if let Some(partition_key) = partition_key {
+                    if partition_key != session_id {
+                        return Err(session_id_mismatch(name, partition_key, session_id));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn body_bytes(&self) -> Bytes {
+        self.body.clone()
+    }
+
+    /// Adds the headers describing this message to a send request
+    pub(crate) fn insert_headers(&self, request: &mut Request) -> azure_core::Result<()> {
+        if let Some(content_type) = &self.content_type {
+            request.insert_header(headers::CONTENT_TYPE, content_type);
+        }
+        if let Some(broker_properties) = self.broker_properties_header()? {
+            request.insert_header(BROKER_PROPERTIES, broker_properties);
+        }
+        for (name, value) in &self.application_properties {
+            request.insert_header(headers::HeaderName::from(name.clone()), value.clone());
+        }
+        Ok(())
+    }
+
+    /// Serializes the standard message properties for the `BrokerProperties` header
+    ///
+    /// Returns `None` if no property has been set.
+    fn broker_properties_header(&self) -> azure_core::Result<Option<String>> {
+        if self.broker_properties.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::to_string(&self.broker_properties)?))
+    }
+
+    /// Converts the message into an entry of a batch send request
+    ///
+    /// Batched bodies are sent as JSON strings, so the body must be valid UTF-8.
+    /// The content type is not part of the batch format and is not sent; the
+    /// application properties are sent as its `UserProperties`.
+    pub(crate) fn batch_entry(&self) -> azure_core::Result<BatchEntry<'_>> {
+        let body = std::str::from_utf8(&self.body).context(
+            ErrorKind::DataConversion,
+            "message body must be valid UTF-8 to be sent in a batch",
+        )?;
+        Ok(BatchEntry {
+            body: Cow::Borrowed(body),
+            broker_properties: &self.broker_properties,
+            user_properties: &self.application_properties,
+        })
+    }
+
+    /// The number of bytes the message takes up in the body of a batch send request
+    pub(crate) fn batch_entry_size(&self) -> usize {
+        let entry = BatchEntry {
+            body: String::from_utf8_lossy(&self.body),
+            broker_properties: &self.broker_properties,
+            user_properties: &self.application_properties,
+        };
+        serde_json::to_vec(&entry).map_or(usize::MAX, |entry| entry.len())
+    }
+}
+
+impl std::fmt::Display for ServiceBusMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_message_summary(
+            f,
+            "ServiceBusMessage",
+            self.message_id(),
+            self.session_id(),
+            self.body.len(),
+        )
+    }
+}
+
+/// Checks `value` against the length limit of `field`
+///
+/// Service Bus measures identifiers in UTF-16 code units, like .NET strings,
+/// so a character outside the Basic Multilingual Plane (e.g. an emoji) counts
+/// twice, while any other character counts once regardless of its UTF-8 size.
+fn check_id_length(field: &'static str, value: &str) -> azure_core::Result<()> {
+    if value.encode_utf16().count() > MAX_ID_LENGTH {
+        return Err(Error::new(
+            ErrorKind::Other,
+            ServiceBusError::ValueTooLong {
+                field,
+                max: MAX_ID_LENGTH,
+            },
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `name` is a header of a send request or received message that
+/// carries an application property
+///
+/// `x-ms-` headers are added by Azure and are not application properties either.
+pub(crate) fn is_application_property(name: &str) -> bool {
+    !RESERVED_HEADER_NAMES.contains(&name) && !name.starts_with("x-ms-")
+}
+
+/// Whether `name` is a valid HTTP header name, i.e. a non-empty token
+fn is_header_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+fn session_id_mismatch(field: &'static str, partition_key: &str, session_id: &str) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        ServiceBusError::PartitionKeyMismatch {
+            field,
+            partition_key: partition_key.to_owned(),
+            session_id: session_id.to_owned(),
+        },
+    )
+}
+
+impl From<Bytes> for ServiceBusMessage {
+    /// Uses the buffer as the message body without copying it
+    fn from(body: Bytes) -> Self {
+        Self::new(body)
+    }
+}
+
+impl From<Vec<u8>> for ServiceBusMessage {
+    /// Uses the vector as the message body without copying it
+    fn from(body: Vec<u8>) -> Self {
+        Self::new(body)
+    }
+}
+
+impl From<&[u8]> for ServiceBusMessage {
+    /// Copies the slice into the message body
+    fn from(body: &[u8]) -> Self {
+        Self::new(Bytes::copy_from_slice(body))
+    }
+}
+
+impl From<String> for ServiceBusMessage {
+    /// Uses the UTF-8 bytes of the string as the message body without copying them
+    fn from(body: String) -> Self {
+        Self::new(body)
+    }
+}
+
+impl From<&str> for ServiceBusMessage {
+    /// Copies the UTF-8 bytes of the string into the message body
+    fn from(body: &str) -> Self {
+        Self::new(Bytes::copy_from_slice(body.as_bytes()))
+    }
+}
+
+impl From<&PeekLockResponse> for ServiceBusMessage {
+    /// Creates a message to send a received message again, e.g. to move it
+    /// from a dead-letter queue back to its queue
+    ///
+    /// The body, the content type, the application properties and the standard
+    /// properties set by the sender, such as the message id, correlation id and
+    /// subject, are copied. Properties assigned by Service Bus, such as the
+    /// sequence number, lock and delivery count, are not, and neither is the
+    /// time to live, so the new message gets the default of the entity it is
+    /// sent to.
+    fn from(received: &PeekLockResponse) -> Self {
+        let owned = |value: Option<&str>| value.map(ToOwned::to_owned);
+
+        Self {
+            body: received.body.clone(),
+            content_type: owned(received.content_type()),
+            broker_properties: SendBrokerProperties {
+                correlation_id: owned(received.correlation_id()),
+                label: owned(received.subject()),
+                message_id: owned(received.message_id()),
+                partition_key: owned(received.partition_key()),
+                reply_to: owned(received.reply_to()),
+                reply_to_session_id: owned(received.reply_to_session_id()),
+                session_id: owned(received.session_id()),
+                to: owned(received.to()),
+                ..SendBrokerProperties::default()
+            },
+            application_properties: received.application_properties(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_broker_properties_header_by_default() {
+        let message = ServiceBusMessage::from("hello");
+        assert_eq!(message.broker_properties_header().unwrap(), None);
+    }
+
+    #[test]
+    fn correlation_id_is_sent_as_broker_property() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_correlation_id("request-1");
+
+        assert_eq!(message.correlation_id(), Some("request-1"));
+        assert_eq!(
+            message.broker_properties_header().unwrap().as_deref(),
+            Some(r#"{"CorrelationId":"request-1"}"#)
+        );
+    }
+
+    #[test]
+    fn message_id_is_sent_as_broker_property() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_message_id("order-42").unwrap();
+
+        assert_eq!(message.message_id(), Some("order-42"));
+        assert_eq!(
+            message.broker_properties_header().unwrap().as_deref(),
+            Some(r#"{"MessageId":"order-42"}"#)
+        );
+    }
+
+    #[test]
+    fn subject_is_sent_as_label() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_subject("orders/created");
+
+        assert_eq!(message.subject(), Some("orders/created"));
+        assert_eq!(
+            message.broker_properties_header().unwrap().as_deref(),
+            Some(r#"{"Label":"orders/created"}"#)
+        );
+    }
+
+    #[test]
+    fn reply_to_properties_are_sent_as_broker_properties() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_reply_to("replies");
+        message.set_reply_to_session_id("session-1").unwrap();
+
+        assert_eq!(message.reply_to(), Some("replies"));
+        assert_eq!(message.reply_to_session_id(), Some("session-1"));
+        assert_eq!(
+            message.broker_properties_header().unwrap().as_deref(),
+            Some(r#"{"ReplyTo":"replies","ReplyToSessionId":"session-1"}"#)
+        );
+    }
+
+    #[test]
+    fn time_to_live_is_sent_in_seconds() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_time_to_live(Duration::from_millis(5_500));
+
+        assert_eq!(message.time_to_live(), Some(Duration::from_millis(5_500)));
+        assert_eq!(
+            message.broker_properties_header().unwrap().as_deref(),
+            Some(r#"{"TimeToLive":5.5}"#)
+        );
+    }
+
+    #[test]
+    fn to_is_sent_as_broker_property() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_to("inventory");
+
+        assert_eq!(message.to(), Some("inventory"));
+        assert_eq!(
+            message.broker_properties_header().unwrap().as_deref(),
+            Some(r#"{"To":"inventory"}"#)
+        );
+    }
+
+    #[test]
+    fn partition_key_must_match_session_id() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_session_id("session-1").unwrap();
+
+        assert_eq!(
+            message
+                .set_partition_key("session-2")
+                .unwrap_err()
+                .downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::PartitionKeyMismatch {
+                field: "PartitionKey",
+                partition_key: "session-2".into(),
+                session_id: "session-1".into(),
+            })
+        );
+        assert_eq!(message.partition_key(), None);
+
+        message.set_partition_key("session-1").unwrap();
+        assert_eq!(message.partition_key(), Some("session-1"));
+        assert_eq!(
+            message.broker_properties_header().unwrap().as_deref(),
+            Some(r#"{"PartitionKey":"session-1","SessionId":"session-1"}"#)
+        );
+    }
+
+    #[test]
+    fn via_partition_key_must_match_session_id() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_via_partition_key("key-1").unwrap();
+        assert_eq!(
+            message.broker_properties_header().unwrap().as_deref(),
+            Some(r#"{"ViaPartitionKey":"key-1"}"#)
+        );
+
+        message.set_session_id("session-1").unwrap();
+        assert_eq!(message.via_partition_key(), Some("session-1"));
+        assert_eq!(
+            message
+                .set_via_partition_key("key-1")
+                .unwrap_err()
+                .downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::PartitionKeyMismatch {
+                field: "ViaPartitionKey",
+                partition_key: "key-1".into(),
+                session_id: "session-1".into(),
+            })
+        );
+        assert_eq!(message.via_partition_key(), Some("session-1"));
+        assert!(message.validate().is_ok());
+    }
+
+    #[test]
+    fn session_id_overrides_partition_key() {
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_partition_key("key-1").unwrap();
+        message.set_session_id("session-1").unwrap();
+
+        assert_eq!(message.session_id(), Some("session-1"));
+        assert_eq!(message.partition_key(), Some("session-1"));
+    }
+
+    #[test]
+    fn ids_are_limited_to_128_code_units() {
+        let at_limit = "a".repeat(MAX_ID_LENGTH);
+        let over_limit = "a".repeat(MAX_ID_LENGTH + 1);
+        let mut message = ServiceBusMessage::from("hello");
+
+        assert!(message.set_message_id(at_limit.as_str()).is_ok());
+        assert!(message.set_session_id(at_limit.as_str()).is_ok());
+        assert!(message.set_partition_key(at_limit.as_str()).is_ok());
+        assert!(message.set_via_partition_key(at_limit.as_str()).is_ok());
+        assert!(message.set_reply_to_session_id(at_limit.as_str()).is_ok());
+
+        for (field, result) in [
+            ("MessageId", message.set_message_id(over_limit.as_str())),
+            ("SessionId", message.set_session_id(over_limit.as_str())),
+            (
+                "PartitionKey",
+                message.set_partition_key(over_limit.as_str()),
+            ),
+            (
+                "ViaPartitionKey",
+                message.set_via_partition_key(over_limit.as_str()),
+            ),
+            (
+                "ReplyToSessionId",
+                message.set_reply_to_session_id(over_limit.as_str()),
+            ),
+        ] {
+            assert_eq!(
+                result.unwrap_err().downcast_ref::<ServiceBusError>(),
+                Some(&ServiceBusError::ValueTooLong {
+                    field,
+                    max: MAX_ID_LENGTH
+                })
+            );
+        }
+        assert_eq!(message.message_id(), Some(at_limit.as_str()));
+    }
+
+    #[test]
+    fn id_length_counts_utf16_code_units() {
+        let mut message = ServiceBusMessage::from("hello");
+
+        assert!(message.set_message_id("é".repeat(MAX_ID_LENGTH)).is_ok());
+        assert!(message
+            .set_message_id("é".repeat(MAX_ID_LENGTH + 1))
+            .is_err());
+
+        // characters outside the Basic Multilingual Plane take two code units
+        assert!(message
+            .set_message_id("😀".repeat(MAX_ID_LENGTH / 2))
+            .is_ok());
+        assert!(message
+            .set_message_id(format!("{}a", "😀".repeat(MAX_ID_LENGTH / 2)))
+            .is_err());
+    }
+
+    #[test]
+    fn validate_rejects_oversized_body() {
+        let message = ServiceBusMessage::from(vec![0; DEFAULT_MAX_MESSAGE_SIZE_IN_BYTES + 1]);
+        assert_eq!(
+            message
+                .validate()
+                .unwrap_err()
+                .downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::MessageSizeExceeded)
+        );
+        assert!(message.validate_with_max_size_in_bytes(1024 * 1024).is_ok());
+
+        let message = ServiceBusMessage::from(vec![0; DEFAULT_MAX_MESSAGE_SIZE_IN_BYTES]);
+        assert!(message.validate().is_ok());
+        assert!(message.validate_with_max_size_in_bytes(1024).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_partition_key_not_matching_session_id() {
+        let message = ServiceBusMessage {
+            broker_properties: SendBrokerProperties {
+                partition_key: Some("key-1".into()),
+                session_id: Some("session-1".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            message
+                .validate()
+                .unwrap_err()
+                .downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::PartitionKeyMismatch {
+                field: "PartitionKey",
+                partition_key: "key-1".into(),
+                session_id: "session-1".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn display_is_compact() {
+        let mut message = ServiceBusMessage::from("hello");
+        assert_eq!(message.to_string(), "ServiceBusMessage { size_bytes: 5 }");
+
+        message.set_message_id("order-42").unwrap();
+        message.set_session_id("session-1").unwrap();
+        assert_eq!(
+            message.to_string(),
+            r#"ServiceBusMessage { id: "order-42", session: "session-1", size_bytes: 5 }"#
+        );
+    }
+
+    #[test]
+    fn application_properties_are_sent_as_headers() {
+        let mut message = ServiceBusMessage::from("hello");
+        message
+            .set_application_property("Priority", "High")
+            .unwrap();
+        message.set_application_property("x-retries", "3").unwrap();
+        let mut request = Request::new(
+            "https://namespace.servicebus.windows.net/queue/messages"
+                .parse()
+                .unwrap(),
+            azure_core::Method::Post,
+        );
+
+        message.insert_headers(&mut request).unwrap();
+
+        assert_eq!(
+            message
+                .application_properties()
+                .get("priority")
+                .map(String::as_str),
+            Some("High")
+        );
+        assert_eq!(
+            request
+                .headers()
+                .get_optional_str(&headers::HeaderName::from_static("priority")),
+            Some("High")
+        );
+        assert_eq!(
+            request
+                .headers()
+                .get_optional_str(&headers::HeaderName::from_static("x-retries")),
+            Some("3")
+        );
+    }
+
+    #[test]
+    fn invalid_application_properties_are_rejected() {
+        let mut message = ServiceBusMessage::from("hello");
+
+        for (name, value, reason) in [
+            ("has space", "value", "not a valid header name"),
+            ("", "value", "not a valid header name"),
+            (
+                "Content-Type",
+                "text/plain",
+                "the header is reserved by Service Bus",
+            ),
+            (
+                "BrokerProperties",
+                "{}",
+                "the header is reserved by Service Bus",
+            ),
+            (
+                "priority",
+                "line\r\nbreak",
+                "the value contains control characters",
+            ),
+        ] {
+            assert_eq!(
+                message
+                    .set_application_property(name, value)
+                    .unwrap_err()
+                    .downcast_ref::<ServiceBusError>(),
+                Some(&ServiceBusError::InvalidApplicationProperty {
+                    name: name.to_ascii_lowercase(),
+                    reason
+                })
+            );
+        }
+        assert!(message.application_properties().is_empty());
+    }
+
+    #[test]
+    fn content_type_is_sent_as_header() {
+        let mut message = ServiceBusMessage::from("{}");
+        message.set_content_type("application/json");
+        let mut request = Request::new(
+            "https://namespace.servicebus.windows.net/queue/messages"
+                .parse()
+                .unwrap(),
+            azure_core::Method::Post,
+        );
+
+        message.insert_headers(&mut request).unwrap();
+
+        assert_eq!(message.content_type(), Some("application/json"));
+        assert_eq!(
+            request.headers().get_optional_str(&headers::CONTENT_TYPE),
+            Some("application/json")
+        );
+        assert_eq!(request.headers().get_optional_str(&BROKER_PROPERTIES), None);
+    }
+}
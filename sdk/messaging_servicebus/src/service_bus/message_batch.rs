@@ -0,0 +1,236 @@
+use bytes::Bytes;
+
+use super::ServiceBusMessage;
+
+/// Largest batch accepted by a Standard tier namespace
+const DEFAULT_MAX_BATCH_SIZE_IN_BYTES: usize = 256 * 1024;
+
+/// Size of the enclosing `[]` of the batch body
+const EMPTY_BATCH_SIZE_IN_BYTES: usize = 2;
+
+/// A batch of messages to be sent to a queue or topic in a single request
+///
+/// Messages are added with [`try_add_message`](Self::try_add_message) until
+/// the batch is full. Sending a batch borrows it, so if the send fails the
+/// batch can be sent again, or taken apart with `into_iter` to rebuild smaller
+/// batches from its messages.
+///
+/// Batched bodies are sent as JSON strings, so every message body must be
+/// valid UTF-8 and the content type of the messages is not sent.
+#[derive(Debug, Clone)]
+pub struct ServiceBusMessageBatch {
+    messages: Vec<ServiceBusMessage>,
+    size_in_bytes: usize,
+    max_size_in_bytes: usize,
+}
+
+impl ServiceBusMessageBatch {
+    /// Creates an empty batch limited to the 256 KB accepted by a Standard tier namespace
+    pub fn new() -> Self {
+        Self::with_max_size_in_bytes(DEFAULT_MAX_BATCH_SIZE_IN_BYTES)
+    }
+
+    /// Creates an empty batch limited to `max_size_in_bytes`
+    ///
+    /// Use this to make use of the larger batches accepted by Premium tier namespaces.
+    pub fn with_max_size_in_bytes(max_size_in_bytes: usize) -> Self {
+        Self {
+            messages: Vec::new(),
+            size_in_bytes: EMPTY_BATCH_SIZE_IN_BYTES,
+            max_size_in_bytes,
+        }
+    }
+
+    /// Adds a message to the batch if it fits
+    ///
+    /// If adding the message would exceed the maximum size of the batch, the
+    /// message is handed back so it can start the next batch without a clone:
+    ///
+    /// ```
+    /// use azure_messaging_servicebus::prelude::*;
+    ///
+    /// let mut batches = vec![ServiceBusMessageBatch::with_max_size_in_bytes(64)];
+    /// for i in 0..10 {
+    ///     let message = ServiceBusMessage::from(format!("message {i}"));
+    ///     if let Err(message) = batches.last_mut().unwrap().try_add_message(message) {
+    ///         let mut batch = ServiceBusMessageBatch::with_max_size_in_bytes(64);
+    ///         batch.try_add_message(message).expect("message larger than a batch");
+    ///         batches.push(batch);
+    ///     }
+    /// }
+    /// assert!(batches.len() > 1);
+    /// ```
+    // the message is handed back by value to avoid boxing it on every overflow
+    #[allow(clippy::result_large_err)]
+    pub fn try_add_message<M>(&mut self, message: M) -> Result<(), ServiceBusMessage>
+    where
+        M: Into<ServiceBusMessage>,
+    {
+        let message = message.into();
+        let separator_size = usize::from(!self.messages.is_empty());
+        let size_in_bytes = self
+            .size_in_bytes
+            .saturating_add(separator_size)
+            .saturating_add(message.batch_entry_size());
+        if size_in_bytes > self.max_size_in_bytes {
+            return Err(message);
+        }
+
+        self.size_in_bytes = size_in_bytes;
+        self.messages.push(message);
+        Ok(())
+    }
+
+    /// The messages in the batch
+    pub fn messages(&self) -> &[ServiceBusMessage] {
+        &self.messages
+    }
+
+    /// The number of messages in the batch
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Whether the batch contains no messages
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// The size of the batch request body in bytes
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+
+    /// The maximum size of the batch request body in bytes
+    pub fn max_size_in_bytes(&self) -> usize {
+        self.max_size_in_bytes
+    }
+
+    /// Removes all messages from the batch, keeping its allocation and maximum size
+    ///
+    /// This lets a batch be refilled and sent again once it has been sent.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.size_in_bytes = EMPTY_BATCH_SIZE_IN_BYTES;
+    }
+
+    /// Serializes the batch into the body of a batch send request
+    pub(crate) fn to_json(&self) -> azure_core::Result<Bytes> {
+        let entries = self
+            .messages
+            .iter()
+            .map(|message| {
+                message.validate_with_max_size_in_bytes(self.max_size_in_bytes)?;
+                message.batch_entry()
+            })
+            .collect::<azure_core::Result<Vec<_>>>()?;
+        Ok(serde_json::to_vec(&entries)?.into())
+    }
+}
+
+impl Default for ServiceBusMessageBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntoIterator for ServiceBusMessageBatch {
+    type Item = ServiceBusMessage;
+    type IntoIter = std::vec::IntoIter<ServiceBusMessage>;
+
+    /// Consumes the batch, returning its messages in the order they were added
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_matches_serialized_batch() {
+        let mut batch = ServiceBusMessageBatch::new();
+        assert_eq!(batch.size_in_bytes(), batch.to_json().unwrap().len());
+
+        batch.try_add_message("first").unwrap();
+        let mut message = ServiceBusMessage::from("second");
+        message.set_message_id("message-2").unwrap();
+        batch.try_add_message(message).unwrap();
+
+        let json = batch.to_json().unwrap();
+        assert_eq!(
+            json,
+            r#"[{"Body":"first"},{"Body":"second","BrokerProperties":{"MessageId":"message-2"}}]"#
+        );
+        assert_eq!(batch.size_in_bytes(), json.len());
+    }
+
+    #[test]
+    fn application_properties_are_sent_as_user_properties() {
+        let mut batch = ServiceBusMessageBatch::new();
+        let mut message = ServiceBusMessage::from("hello");
+        message
+            .set_application_property("Priority", "High")
+            .unwrap();
+        batch.try_add_message(message).unwrap();
+
+        let json = batch.to_json().unwrap();
+        assert_eq!(
+            json,
+            r#"[{"Body":"hello","UserProperties":{"priority":"High"}}]"#
+        );
+        assert_eq!(batch.size_in_bytes(), json.len());
+    }
+
+    #[test]
+    fn rejects_message_exceeding_max_size() {
+        let mut batch = ServiceBusMessageBatch::with_max_size_in_bytes(32);
+
+        batch.try_add_message("fits").unwrap();
+        assert_eq!(
+            batch.try_add_message("does not fit anymore"),
+            Err(ServiceBusMessage::from("does not fit anymore"))
+        );
+        assert_eq!(batch.len(), 1);
+        assert!(batch.size_in_bytes() <= batch.max_size_in_bytes());
+    }
+
+    #[test]
+    fn rejects_binary_body_on_send() {
+        let mut batch = ServiceBusMessageBatch::new();
+        batch.try_add_message(vec![0xff, 0xfe]).unwrap();
+
+        assert!(batch.to_json().is_err());
+    }
+
+    #[test]
+    fn clear_resets_size_and_keeps_max_size() {
+        let mut batch = ServiceBusMessageBatch::with_max_size_in_bytes(32);
+        batch.try_add_message("first").unwrap();
+
+        batch.clear();
+        assert!(batch.is_empty());
+        assert_eq!(batch.size_in_bytes(), batch.to_json().unwrap().len());
+        assert_eq!(batch.max_size_in_bytes(), 32);
+
+        batch.try_add_message("second").unwrap();
+        assert_eq!(batch.messages(), [ServiceBusMessage::from("second")]);
+    }
+
+    #[test]
+    fn into_iter_returns_messages_in_order() {
+        let mut batch = ServiceBusMessageBatch::new();
+        batch.try_add_message("first").unwrap();
+        batch.try_add_message("second").unwrap();
+
+        let messages: Vec<_> = batch.into_iter().collect();
+        assert_eq!(
+            messages,
+            vec![
+                ServiceBusMessage::from("first"),
+                ServiceBusMessage::from("second")
+            ]
+        );
+    }
+}
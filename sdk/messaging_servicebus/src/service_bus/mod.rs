@@ -1,22 +1,52 @@
 use azure_core::{
     base64, error::Error, headers, CollectedResponse, HttpClient, Method, Request, StatusCode, Url,
 };
+use bytes::Bytes;
+use futures::StreamExt;
 use ring::hmac;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use std::time::Duration;
 use std::{ops::Add, sync::Arc};
 use time::OffsetDateTime;
 use url::form_urlencoded::{self, Serializer};
 
+mod connection_string;
+mod custom_endpoint;
+mod error;
+#[cfg(any(test, feature = "test-util"))]
+mod loopback;
+mod message;
+mod message_batch;
 mod queue_client;
+mod telemetry;
 mod topic_client;
 
-use crate::utils::{craft_peek_lock_url, get_head_url};
+use crate::utils::{craft_peek_lock_url, entity_url, get_head_url};
 
+pub use self::connection_string::ServiceBusConnectionStringProperties;
+pub use self::error::ServiceBusError;
+#[cfg(any(test, feature = "test-util"))]
+pub use self::loopback::LoopbackHttpClient;
+pub use self::message::ServiceBusMessage;
+pub use self::message_batch::ServiceBusMessageBatch;
 pub use self::queue_client::QueueClient;
 pub use self::topic_client::{SubscriptionReceiver, TopicClient, TopicSender};
 
+/// Value of the `messaging.system` attribute of tracing spans
+#[cfg(feature = "tracing")]
+const MESSAGING_SYSTEM: &str = "servicebus";
+
+/// Content type of the body of a batch send request
+const BATCH_CONTENT_TYPE: &str = "application/vnd.microsoft.servicebus.json";
+
+/// Header carrying the standard message properties as JSON
+const BROKER_PROPERTIES: headers::HeaderName = headers::HeaderName::from_static("brokerproperties");
+
+/// Most settlement requests sent at once by `complete_messages` and `abandon_messages`
+const MAX_CONCURRENT_SETTLEMENTS: usize = 8;
+
 /// Default duration for the SAS token in days — We might want to make this configurable at some point
 const DEFAULT_SAS_DURATION: u64 = 3_600; // seconds = 1 hour
 
@@ -24,7 +54,7 @@ const DEFAULT_SAS_DURATION: u64 = 3_600; // seconds = 1 hour
 fn finalize_request(
     url: &str,
     method: azure_core::Method,
-    body: Option<String>,
+    body: Option<Bytes>,
     policy_name: &str,
     signing_key: &hmac::Key,
 ) -> azure_core::Result<Request> {
@@ -80,32 +110,110 @@ fn generate_signature(
 }
 
 /// Sends a message to the queue or topic
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "ServiceBus.send",
+        skip_all,
+        err,
+        fields(
+            messaging.system = MESSAGING_SYSTEM,
+            messaging.destination = queue_or_topic,
+            messaging.operation = "publish",
+        )
+    )
+)]
 async fn send_message(
     http_client: &Arc<dyn HttpClient>,
     namespace: &str,
     queue_or_topic: &str,
     policy_name: &str,
     signing_key: &hmac::Key,
-    msg: &str,
+    max_message_size_in_bytes: usize,
+    msg: &ServiceBusMessage,
 ) -> azure_core::Result<()> {
-    let url = format!("https://{namespace}.servicebus.windows.net/{queue_or_topic}/messages");
+    msg.validate_with_max_size_in_bytes(max_message_size_in_bytes)?;
+
+    let url = entity_url(namespace, queue_or_topic, None, &["messages"])?;
 
-    let req = finalize_request(
-        &url,
+    let mut req = finalize_request(
+        url.as_ref(),
         Method::Post,
-        Some(msg.to_string()),
+        Some(msg.body_bytes()),
         policy_name,
         signing_key,
     )?;
 
-    http_client
-        .as_ref()
-        .execute_request_check_status(&req)
-        .await?;
+    msg.insert_headers(&mut req)?;
+
+    telemetry::record(
+        queue_or_topic,
+        "send",
+        error::execute_request_check_status(http_client.as_ref(), &req),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Sends a batch of messages to the queue or topic in a single request
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "ServiceBus.send_batch",
+        skip_all,
+        err,
+        fields(
+            messaging.system = MESSAGING_SYSTEM,
+            messaging.destination = queue_or_topic,
+            messaging.operation = "publish",
+        )
+    )
+)]
+async fn send_message_batch(
+    http_client: &Arc<dyn HttpClient>,
+    namespace: &str,
+    queue_or_topic: &str,
+    policy_name: &str,
+    signing_key: &hmac::Key,
+    batch: &ServiceBusMessageBatch,
+) -> azure_core::Result<()> {
+    let body = batch.to_json()?;
+
+    let url = entity_url(namespace, queue_or_topic, None, &["messages"])?;
+
+    let mut req = finalize_request(
+        url.as_ref(),
+        Method::Post,
+        Some(body),
+        policy_name,
+        signing_key,
+    )?;
+    req.insert_header(headers::CONTENT_TYPE, BATCH_CONTENT_TYPE);
+
+    telemetry::record(
+        queue_or_topic,
+        "send_batch",
+        error::execute_request_check_status(http_client.as_ref(), &req),
+    )
+    .await?;
     Ok(())
 }
 
 /// Receive and delete a message
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "ServiceBus.receive_and_delete",
+        skip_all,
+        err,
+        fields(
+            messaging.system = MESSAGING_SYSTEM,
+            messaging.destination = queue_or_topic,
+            messaging.destination.subscription.name = subscription,
+            messaging.operation = "receive",
+        )
+    )
+)]
 async fn receive_and_delete_message(
     http_client: &Arc<dyn HttpClient>,
     namespace: &str,
@@ -117,10 +225,12 @@ async fn receive_and_delete_message(
     let url = get_head_url(namespace, queue_or_topic, subscription);
     let req = finalize_request(&url, Method::Delete, None, policy_name, signing_key)?;
 
-    http_client
-        .as_ref()
-        .execute_request_check_status(&req)
-        .await
+    telemetry::record(
+        &telemetry::entity_path(queue_or_topic, subscription),
+        "receive_and_delete",
+        error::execute_request_check_status(http_client.as_ref(), &req),
+    )
+    .await
 }
 
 /// Non-destructively read a message
@@ -131,6 +241,20 @@ async fn receive_and_delete_message(
 /// the message can be consumed by others. If you want to keep
 /// track of this message (i.e., have the possibility of deletion),
 /// use `peek_lock_message2`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "ServiceBus.peek_lock",
+        skip_all,
+        err,
+        fields(
+            messaging.system = MESSAGING_SYSTEM,
+            messaging.destination = queue_or_topic,
+            messaging.destination.subscription.name = subscription,
+            messaging.operation = "receive",
+        )
+    )
+)]
 async fn peek_lock_message(
     http_client: &Arc<dyn HttpClient>,
     namespace: &str,
@@ -144,16 +268,35 @@ async fn peek_lock_message(
 
     let req = finalize_request(url.as_ref(), Method::Post, None, policy_name, signing_key)?;
 
-    http_client
-        .as_ref()
-        .execute_request_check_status(&req)
-        .await
+    telemetry::record(
+        &telemetry::entity_path(queue_or_topic, subscription),
+        "peek_lock",
+        error::execute_request_check_status(http_client.as_ref(), &req),
+    )
+    .await
 }
 
 /// Non-destructively read a message but track it
 ///
 /// Note: This function returns a `PeekLockResponse`
 /// that contains a helper `delete_message` function.
+///
+/// A response with an error status is returned as an error carrying the
+/// [`ServiceBusError`] it signals, and recorded as a failure by telemetry.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "ServiceBus.peek_lock",
+        skip_all,
+        err,
+        fields(
+            messaging.system = MESSAGING_SYSTEM,
+            messaging.destination = queue_or_topic,
+            messaging.destination.subscription.name = subscription,
+            messaging.operation = "receive",
+        )
+    )
+)]
 async fn peek_lock_message2(
     http_client: &Arc<dyn HttpClient>,
     namespace: &str,
@@ -167,17 +310,25 @@ async fn peek_lock_message2(
 
     let req = finalize_request(url.as_ref(), Method::Post, None, policy_name, signing_key)?;
 
-    let res = http_client.execute_request(&req).await?;
+    let entity_path = telemetry::entity_path(queue_or_topic, subscription);
+    let res = telemetry::record(
+        &entity_path,
+        "peek_lock",
+        error::execute_request_check_status(http_client.as_ref(), &req),
+    )
+    .await?;
 
-    let status = res.status();
+    let status = *res.status();
     let headers = res.headers().clone();
-    let broker_properties = res
-        .headers()
-        .get_optional_as(&headers::HeaderName::from("brokerproperties"))?;
+    let broker_properties: Option<BrokerProperties> =
+        headers.get_optional_as(&BROKER_PROPERTIES)?;
     let lock_location = headers
         .get_optional_string(&headers::LOCATION)
         .unwrap_or_default();
-    let body = res.into_body().collect_string().await?;
+    let body = res.body().clone();
+    let lock_duration = broker_properties
+        .as_ref()
+        .map(|broker_properties| broker_properties.locked_until_utc - OffsetDateTime::now_utc());
 
     Ok(PeekLockResponse {
         body,
@@ -188,12 +339,85 @@ async fn peek_lock_message2(
         http_client: http_client.clone(),
         policy_name: policy_name.to_owned(),
         signing_key: signing_key.to_owned(),
+        entity_path,
+        lock_duration,
     })
 }
 
+/// Non-destructively read a message, waiting up to `timeout` for one to arrive
+///
+/// Returns `None` if the queue or subscription is still empty once the timeout
+/// has elapsed. The service waits in whole seconds, so a `timeout` of zero
+/// returns immediately. Any status other than a locked message or an empty
+/// entity is returned as an error.
+async fn peek_lock_message_with_timeout(
+    http_client: &Arc<dyn HttpClient>,
+    namespace: &str,
+    queue_or_topic: &str,
+    policy_name: &str,
+    signing_key: &hmac::Key,
+    timeout: Duration,
+    subscription: Option<&str>,
+) -> azure_core::Result<Option<PeekLockResponse>> {
+    let response = peek_lock_message2(
+        http_client,
+        namespace,
+        queue_or_topic,
+        policy_name,
+        signing_key,
+        Some(timeout),
+        subscription,
+    )
+    .await?;
+
+    match response.status {
+        StatusCode::Ok | StatusCode::Created => Ok(Some(response)),
+        StatusCode::NoContent => Ok(None),
+        status => Err(error::status_error(
+            status,
+            &response.headers,
+            &response.body,
+        )),
+    }
+}
+
+/// Completes the messages, returning the outcome for each message in order
+///
+/// The REST API settles a single message per request, so up to
+/// [`MAX_CONCURRENT_SETTLEMENTS`] requests are sent concurrently; a failure to
+/// complete one message does not affect the others.
+async fn complete_messages(messages: &[PeekLockResponse]) -> Vec<Result<(), Error>> {
+    futures::stream::iter(
+        messages
+            .iter()
+            .map(|message| async move { message.delete_message().await.map(|_| ()) }),
+    )
+    .buffered(MAX_CONCURRENT_SETTLEMENTS)
+    .collect()
+    .await
+}
+
+/// Abandons the messages, returning the outcome for each message in order
+///
+/// Like [`complete_messages`], one request is sent per message, with up to
+/// [`MAX_CONCURRENT_SETTLEMENTS`] at once.
+async fn abandon_messages(messages: &[PeekLockResponse]) -> Vec<Result<(), Error>> {
+    futures::stream::iter(messages.iter().map(PeekLockResponse::unlock_message))
+        .buffered(MAX_CONCURRENT_SETTLEMENTS)
+        .collect()
+        .await
+}
+
 /// `PeekLockResponse` object that is returned by `peek_lock_message2`
+///
+/// Cloning copies the body and properties of the message, and the clone keeps
+/// the location of the lock: the message can be settled through any clone, and
+/// once it is, settling it through another fails with
+/// [`ServiceBusError::MessageLockExpired`]. The lock expiry of a clone is not
+/// updated when the lock is renewed through another clone.
+#[derive(Clone)]
 pub struct PeekLockResponse {
-    body: String,
+    body: Bytes,
     headers: headers::Headers,
     broker_properties: Option<BrokerProperties>,
     lock_location: String,
@@ -201,12 +425,24 @@ pub struct PeekLockResponse {
     http_client: Arc<dyn HttpClient>,
     policy_name: String,
     signing_key: hmac::Key,
+    /// Queue or subscription the message was received from, recorded by telemetry
+    entity_path: String,
+    /// How long the lock was held for when the message was received
+    lock_duration: Option<time::Duration>,
 }
 
 impl PeekLockResponse {
     /// Get the message in the lock
+    ///
+    /// Any invalid UTF-8 sequences in the body are replaced with `U+FFFD`;
+    /// use [`body_as_bytes`](Self::body_as_bytes) for binary payloads.
     pub fn body(&self) -> String {
-        self.body.clone()
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Get the raw bytes of the message in the lock
+    pub fn body_as_bytes(&self) -> &[u8] {
+        &self.body
     }
 
     /// Get the broker properties from the message in the lock
@@ -215,6 +451,146 @@ impl PeekLockResponse {
         self.broker_properties.clone()
     }
 
+    /// Get the content type of the message in the lock
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.get_optional_str(&headers::CONTENT_TYPE)
+    }
+
+    /// Get the application specific subject of the message in the lock
+    pub fn subject(&self) -> Option<&str> {
+        self.broker_properties.as_ref()?.label.as_deref()
+    }
+
+    /// Get the number of times the message in the lock has been delivered
+    ///
+    /// This includes the current delivery and is incremented by the service
+    /// every time the lock expires or the message is unlocked, so it can be
+    /// used to decide whether to give up on a message before settling it.
+    pub fn delivery_count(&self) -> Option<i32> {
+        self.broker_properties
+            .as_ref()
+            .map(|broker_properties| broker_properties.delivery_count)
+    }
+
+    /// Get the instant at which the lock on the message expires
+    ///
+    /// The message must be deleted, unlocked or have its lock renewed before
+    /// then; afterwards it becomes available to other receivers again.
+    pub fn lock_expires_at(&self) -> Option<OffsetDateTime> {
+        self.broker_properties
+            .as_ref()
+            .map(|broker_properties| broker_properties.locked_until_utc)
+    }
+
+    /// Get the time to live of the message in the lock
+    pub fn time_to_live(&self) -> Option<Duration> {
+        self.broker_properties
+            .as_ref()
+            .and_then(|broker_properties| {
+                Duration::try_from_secs_f64(broker_properties.time_to_live).ok()
+            })
+    }
+
+    /// Get the instant the message in the lock expires
+    ///
+    /// `None` is returned if the message never expires in practice, which is
+    /// the case for messages sent without a time to live to an entity without
+    /// a default.
+    pub fn expires_at(&self) -> Option<OffsetDateTime> {
+        let enqueued_at = self.broker_properties.as_ref()?.enqueued_time_utc?;
+        let time_to_live = time::Duration::try_from(self.time_to_live()?).ok()?;
+        enqueued_at.checked_add(time_to_live)
+    }
+
+    /// Get the identifier of the message in the lock
+    pub fn message_id(&self) -> Option<&str> {
+        self.broker_properties
+            .as_ref()
+            .map(|broker_properties| broker_properties.message_id.as_str())
+    }
+
+    /// Get the correlation identifier of the message in the lock
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.broker_properties.as_ref()?.correlation_id.as_deref()
+    }
+
+    /// Get the partition key of the message in the lock
+    ///
+    /// On a partitioned entity this is the key that decided which partition
+    /// the message was stored on.
+    pub fn partition_key(&self) -> Option<&str> {
+        self.broker_properties.as_ref()?.partition_key.as_deref()
+    }
+
+    /// Get the session the message in the lock belongs to
+    pub fn session_id(&self) -> Option<&str> {
+        self.broker_properties.as_ref()?.session_id.as_deref()
+    }
+
+    /// Get the entity the message in the lock was dead-lettered from
+    ///
+    /// This is only set on messages received from a dead-letter queue that
+    /// were forwarded from another entity before being dead-lettered, e.g. in
+    /// the transfer dead-letter queue, and names the entity they came from.
+    pub fn dead_letter_source(&self) -> Option<&str> {
+        self.broker_properties
+            .as_ref()?
+            .dead_letter_source
+            .as_deref()
+    }
+
+    /// Get the state of the message in the lock
+    ///
+    /// `None` is returned if the service did not report a known state.
+    pub fn state(&self) -> Option<ServiceBusMessageState> {
+        ServiceBusMessageState::from_broker_property(&self.broker_properties.as_ref()?.state)
+    }
+
+    /// Get the sequence number the message was first assigned, if it was
+    /// auto-forwarded from another entity
+    ///
+    /// This identifies the message in the entity it was originally sent to,
+    /// which helps to order messages that went through a chain of forwarding.
+    /// The service reports `0` for messages that were not forwarded, which is
+    /// returned as `None`.
+    pub fn enqueued_sequence_number(&self) -> Option<i64> {
+        self.broker_properties
+            .as_ref()?
+            .enqueued_sequence_number
+            .filter(|&sequence_number| sequence_number != 0)
+    }
+
+    /// Get the address replies to the message in the lock should be sent to
+    pub fn reply_to(&self) -> Option<&str> {
+        self.broker_properties.as_ref()?.reply_to.as_deref()
+    }
+
+    /// Get the session replies to the message in the lock should be sent to
+    pub fn reply_to_session_id(&self) -> Option<&str> {
+        self.broker_properties
+            .as_ref()?
+            .reply_to_session_id
+            .as_deref()
+    }
+
+    /// Get the application specific destination address of the message in the lock
+    pub fn to(&self) -> Option<&str> {
+        self.broker_properties.as_ref()?.to.as_deref()
+    }
+
+    /// Get the application properties of the message in the lock, by lowercase name
+    ///
+    /// These are the headers of the response that are not used by the REST API
+    /// itself. Values are returned as the service sent them, e.g. string values
+    /// sent through another protocol may be quoted.
+    pub fn application_properties(&self) -> BTreeMap<String, String> {
+        self.headers
+            .iter()
+            .filter(|(name, _)| message::is_application_property(name.as_str()))
+            .map(|(name, value)| (name.as_str().to_owned(), value.as_str().to_owned()))
+            .collect()
+    }
+
     /// Get custom message headers from the message in the lock
     pub fn custom_properties<T: TryFrom<headers::Headers>>(&self) -> Result<T, T::Error> {
         self.headers.clone().try_into()
@@ -226,6 +602,21 @@ impl PeekLockResponse {
     }
 
     /// Delete message in the lock
+    ///
+    /// Fails with [`ServiceBusError::MessageLockExpired`] if the lock is no longer held.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ServiceBus.complete",
+            skip_all,
+            err,
+            fields(
+                messaging.system = MESSAGING_SYSTEM,
+                messaging.destination = self.entity_path.as_str(),
+                messaging.operation = "settle",
+            )
+        )
+    )]
     pub async fn delete_message(&self) -> azure_core::Result<CollectedResponse> {
         let req = finalize_request(
             &self.lock_location.clone(),
@@ -235,13 +626,31 @@ impl PeekLockResponse {
             &self.signing_key,
         )?;
 
-        self.http_client
-            .as_ref()
-            .execute_request_check_status(&req)
-            .await
+        telemetry::record(
+            &self.entity_path,
+            "complete",
+            error::execute_request_check_status(self.http_client.as_ref(), &req),
+        )
+        .await
+        .map_err(|error| self.map_lock_error(error))
     }
 
     /// Unlock a message in the lock
+    ///
+    /// Fails with [`ServiceBusError::MessageLockExpired`] if the lock is no longer held.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ServiceBus.abandon",
+            skip_all,
+            err,
+            fields(
+                messaging.system = MESSAGING_SYSTEM,
+                messaging.destination = self.entity_path.as_str(),
+                messaging.operation = "settle",
+            )
+        )
+    )]
     pub async fn unlock_message(&self) -> Result<(), Error> {
         let req = finalize_request(
             &self.lock_location.clone(),
@@ -251,15 +660,37 @@ impl PeekLockResponse {
             &self.signing_key,
         )?;
 
-        self.http_client
-            .as_ref()
-            .execute_request_check_status(&req)
-            .await?;
+        telemetry::record(
+            &self.entity_path,
+            "abandon",
+            error::execute_request_check_status(self.http_client.as_ref(), &req),
+        )
+        .await
+        .map_err(|error| self.map_lock_error(error))?;
         Ok(())
     }
 
     /// Renew a message's lock
-    pub async fn renew_message_lock(&self) -> Result<(), Error> {
+    ///
+    /// On success [`lock_expires_at`](Self::lock_expires_at) is moved forward.
+    /// The service does not report the new expiry, so it is estimated as the
+    /// lock duration of the entity, measured when the message was received,
+    /// from now. Fails with [`ServiceBusError::MessageLockExpired`] if the lock
+    /// is no longer held.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ServiceBus.renew_message_lock",
+            skip_all,
+            err,
+            fields(
+                messaging.system = MESSAGING_SYSTEM,
+                messaging.destination = self.entity_path.as_str(),
+                messaging.operation = "renew_lock",
+            )
+        )
+    )]
+    pub async fn renew_message_lock(&mut self) -> Result<(), Error> {
         let req = finalize_request(
             &self.lock_location.clone(),
             Method::Post,
@@ -268,29 +699,74 @@ impl PeekLockResponse {
             &self.signing_key,
         )?;
 
-        self.http_client
-            .as_ref()
-            .execute_request_check_status(&req)
-            .await?;
+        telemetry::record(
+            &self.entity_path,
+            "renew_lock",
+            error::execute_request_check_status(self.http_client.as_ref(), &req),
+        )
+        .await
+        .map_err(|error| self.map_lock_error(error))?;
+
+        if let (Some(broker_properties), Some(lock_duration)) =
+            (self.broker_properties.as_mut(), self.lock_duration)
+        {
+            broker_properties.locked_until_utc = OffsetDateTime::now_utc() + lock_duration;
+        }
         Ok(())
     }
+
+    fn map_lock_error(&self, error: Error) -> Error {
+        error::map_lock_error(
+            error,
+            self.message_id(),
+            self.broker_properties
+                .as_ref()
+                .map(|broker_properties| broker_properties.sequence_number),
+        )
+    }
+}
+
+impl std::fmt::Display for PeekLockResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_message_summary(
+            f,
+            "PeekLockResponse",
+            self.message_id(),
+            self.session_id(),
+            self.body.len(),
+        )
+    }
+}
+
+impl AsRef<[u8]> for PeekLockResponse {
+    fn as_ref(&self) -> &[u8] {
+        self.body_as_bytes()
+    }
 }
 
 /// `BrokerProperties` object decoded from the message headers
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct BrokerProperties {
+    pub correlation_id: Option<String>,
+    pub dead_letter_source: Option<String>,
     pub delivery_count: i32,
-    pub enqueued_sequence_number: Option<i32>,
+    pub enqueued_sequence_number: Option<i64>,
     #[serde(deserialize_with = "BrokerProperties::option_rfc2822")]
     pub enqueued_time_utc: Option<OffsetDateTime>,
+    pub label: Option<String>,
     pub lock_token: String,
     #[serde(with = "time::serde::rfc2822")]
     pub locked_until_utc: OffsetDateTime,
     pub message_id: String,
-    pub sequence_number: i32,
+    pub partition_key: Option<String>,
+    pub reply_to: Option<String>,
+    pub reply_to_session_id: Option<String>,
+    pub sequence_number: i64,
+    pub session_id: Option<String>,
     pub state: String,
-    pub time_to_live: i64,
+    pub time_to_live: f64,
+    pub to: Option<String>,
 }
 
 impl BrokerProperties {
@@ -309,3 +785,148 @@ impl FromStr for BrokerProperties {
         serde_json::from_str(s)
     }
 }
+
+/// The state of a message in its queue or subscription
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ServiceBusMessageState {
+    /// The message can be received
+    Active,
+    /// The message was deferred and can only be received by its sequence number
+    Deferred,
+    /// The message is scheduled and becomes active at its scheduled enqueue time
+    Scheduled,
+}
+
+impl ServiceBusMessageState {
+    /// Maps the `State` broker property, e.g. `Active`
+    fn from_broker_property(state: &str) -> Option<Self> {
+        match state {
+            "Active" => Some(Self::Active),
+            "Deferred" => Some(Self::Deferred),
+            "Scheduled" => Some(Self::Scheduled),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the compact representation of a message used by its `Display` impl
+fn fmt_message_summary(
+    f: &mut std::fmt::Formatter<'_>,
+    type_name: &str,
+    message_id: Option<&str>,
+    session_id: Option<&str>,
+    size_in_bytes: usize,
+) -> std::fmt::Result {
+    write!(f, "{type_name} {{ ")?;
+    if let Some(message_id) = message_id {
+        write!(f, "id: {message_id:?}, ")?;
+    }
+    if let Some(session_id) = session_id {
+        write!(f, "session: {session_id:?}, ")?;
+    }
+    write!(f, "size_bytes: {size_in_bytes} }}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECEIVED_BROKER_PROPERTIES: &str = r#"{"DeliveryCount":1,"EnqueuedSequenceNumber":0,"EnqueuedTimeUtc":"Wed, 02 Jul 2014 01:32:27 GMT","Label":"orders/created","LockToken":"5a2c6e8b-3cd9-4e30-9e6e-9b1b8a6e2ff7","LockedUntilUtc":"Wed, 02 Jul 2014 01:33:27 GMT","MessageId":"31907572164743c38741631acd554d6f","SequenceNumber":2,"State":"Active","TimeToLive":10}"#;
+
+    #[test]
+    fn parse_received_broker_properties() {
+        let broker_properties: BrokerProperties = RECEIVED_BROKER_PROPERTIES.parse().unwrap();
+
+        assert_eq!(broker_properties.delivery_count, 1);
+        assert_eq!(
+            broker_properties.locked_until_utc,
+            OffsetDateTime::from_unix_timestamp(1_404_264_807).unwrap()
+        );
+        assert_eq!(broker_properties.label.as_deref(), Some("orders/created"));
+        assert_eq!(
+            broker_properties.message_id,
+            "31907572164743c38741631acd554d6f"
+        );
+    }
+
+    #[test]
+    fn parse_default_time_to_live() {
+        let broker_properties: BrokerProperties = RECEIVED_BROKER_PROPERTIES
+            .replace(r#""TimeToLive":10"#, r#""TimeToLive":922337203685.47754"#)
+            .parse()
+            .unwrap();
+
+        assert!(broker_properties.time_to_live > 922_337_203_685.0);
+    }
+
+    #[test]
+    fn parse_partitioned_sequence_numbers() {
+        let broker_properties: BrokerProperties = RECEIVED_BROKER_PROPERTIES
+            .replace(
+                r#""EnqueuedSequenceNumber":0"#,
+                r#""EnqueuedSequenceNumber":56294995342131200"#,
+            )
+            .replace(
+                r#""SequenceNumber":2"#,
+                r#""SequenceNumber":56294995342131201"#,
+            )
+            .parse()
+            .unwrap();
+
+        assert_eq!(broker_properties.sequence_number, 56_294_995_342_131_201);
+        assert_eq!(
+            broker_properties.enqueued_sequence_number,
+            Some(56_294_995_342_131_200)
+        );
+    }
+
+    #[test]
+    fn parse_request_reply_broker_properties() {
+        let broker_properties: BrokerProperties = r#"{"CorrelationId":"request-1","DeliveryCount":1,"EnqueuedTimeUtc":"Wed, 02 Jul 2014 01:32:27 GMT","LockToken":"5a2c6e8b-3cd9-4e30-9e6e-9b1b8a6e2ff7","LockedUntilUtc":"Wed, 02 Jul 2014 01:33:27 GMT","MessageId":"reply-1","ReplyTo":"replies","ReplyToSessionId":"session-1","SequenceNumber":3,"State":"Active","TimeToLive":10,"To":"inventory"}"#
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            broker_properties.correlation_id.as_deref(),
+            Some("request-1")
+        );
+        assert_eq!(broker_properties.reply_to.as_deref(), Some("replies"));
+        assert_eq!(
+            broker_properties.reply_to_session_id.as_deref(),
+            Some("session-1")
+        );
+        assert_eq!(broker_properties.to.as_deref(), Some("inventory"));
+    }
+
+    #[test]
+    fn parse_message_state() {
+        for (state, expected) in [
+            ("Active", Some(ServiceBusMessageState::Active)),
+            ("Deferred", Some(ServiceBusMessageState::Deferred)),
+            ("Scheduled", Some(ServiceBusMessageState::Scheduled)),
+            ("Unknown", None),
+        ] {
+            assert_eq!(
+                ServiceBusMessageState::from_broker_property(state),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn parse_dead_letter_source() {
+        let broker_properties: BrokerProperties = RECEIVED_BROKER_PROPERTIES
+            .replace(
+                r#""DeliveryCount":1"#,
+                r#""DeadLetterSource":"orders/subscriptions/billing","DeliveryCount":1"#,
+            )
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            broker_properties.dead_letter_source.as_deref(),
+            Some("orders/subscriptions/billing")
+        );
+    }
+}
@@ -2,28 +2,46 @@ use std::sync::Arc;
 
 use crate::{
     service_bus::{
-        peek_lock_message, peek_lock_message2, receive_and_delete_message, send_message,
-        PeekLockResponse,
+        abandon_messages, complete_messages, custom_endpoint::CustomEndpointHttpClient,
+        message::DEFAULT_MAX_MESSAGE_SIZE_IN_BYTES, peek_lock_message, peek_lock_message2,
+        peek_lock_message_with_timeout, receive_and_delete_message, send_message,
+        send_message_batch, PeekLockResponse, ServiceBusConnectionStringProperties,
+        ServiceBusMessage, ServiceBusMessageBatch,
     },
-    utils::body_bytes_to_utf8,
+    utils::{body_bytes_to_utf8, fully_qualified_namespace},
 };
 use ring::hmac::Key;
 use std::time::Duration;
+use url::Url;
 
 use azure_core::{error::Error, HttpClient};
 
 /// Client object that allows interaction with the `ServiceBus` API
+///
+/// Cloning a client is cheap: clones share the same [`HttpClient`], and with
+/// it its connection pool, so a clone can be handed to each task that needs to
+/// send or receive. Every operation is an independent HTTPS request, so clones
+/// do not contend with each other and throughput scales with the number of
+/// concurrent requests the underlying `HttpClient` allows.
 #[derive(Debug, Clone)]
 pub struct QueueClient {
     http_client: Arc<dyn HttpClient>,
     namespace: String,
+    /// Host name of the namespace, kept for [`Self::fully_qualified_namespace`]
+    fully_qualified_namespace: String,
     queue: String,
     policy_name: String,
     signing_key: Key,
+    /// Largest message body sent, checked before the request is made
+    max_message_size_in_bytes: usize,
 }
 
 impl QueueClient {
     /// Creates a new queue client instance
+    ///
+    /// All requests are sent through `http_client`, so any [`HttpClient`]
+    /// implementation can be used as the transport, e.g. an in-memory fake of
+    /// the service in tests.
     pub fn new<N, Q, P, K>(
         http_client: Arc<dyn HttpClient>,
         namespace: N,
@@ -38,25 +56,126 @@ impl QueueClient {
         K: AsRef<str>,
     {
         let signing_key = Key::new(ring::hmac::HMAC_SHA256, policy_key.as_ref().as_bytes());
+        let namespace = namespace.into();
 
         Ok(QueueClient {
             http_client,
-            namespace: namespace.into(),
+            fully_qualified_namespace: fully_qualified_namespace(&namespace),
+            namespace,
             queue: queue.into(),
             policy_name: policy_name.into(),
             signing_key,
+            max_message_size_in_bytes: DEFAULT_MAX_MESSAGE_SIZE_IN_BYTES,
         })
     }
 
+    /// Creates a new queue client instance from a connection string
+    ///
+    /// The queue is `queue` if given, otherwise the `EntityPath` of the connection
+    /// string. An error is returned if neither is present, if they differ, or if
+    /// the connection string does not contain a shared access key.
+    pub fn from_connection_string(
+        http_client: Arc<dyn HttpClient>,
+        connection_string: &str,
+        queue: Option<&str>,
+    ) -> Result<QueueClient, Error> {
+        let properties = ServiceBusConnectionStringProperties::parse(connection_string)?;
+        let credentials = properties.client_credentials(queue)?;
+
+        Self::new(
+            http_client,
+            credentials.namespace,
+            credentials.entity_path,
+            credentials.policy_name,
+            credentials.policy_key,
+        )
+    }
+
+    /// Sends the requests of this client to `custom_endpoint` instead of
+    /// `<namespace>.servicebus.windows.net`
+    ///
+    /// Use this to reach the namespace through a Private Link endpoint or a proxy.
+    /// Only the scheme, host and port of `custom_endpoint` are used; requests are
+    /// still authorized for the namespace.
+    pub fn with_custom_endpoint(mut self, custom_endpoint: Url) -> Result<Self, Error> {
+        self.http_client = Arc::new(CustomEndpointHttpClient::new(
+            self.http_client,
+            &self.namespace,
+            custom_endpoint,
+        )?);
+        Ok(self)
+    }
+
+    /// Lets this client send message bodies of up to `max_size_in_bytes`
+    ///
+    /// Messages larger than the 256 KB accepted by a Standard tier namespace
+    /// fail with
+    /// [`ServiceBusError::MessageSizeExceeded`](crate::service_bus::ServiceBusError::MessageSizeExceeded) before they are sent.
+    /// Use this to make use of the larger messages accepted by Premium tier namespaces.
+    pub fn with_max_message_size_in_bytes(mut self, max_size_in_bytes: usize) -> Self {
+        self.max_message_size_in_bytes = max_size_in_bytes;
+        self
+    }
+
+    /// The host name of the namespace, e.g. `<namespace>.servicebus.windows.net`
+    pub fn fully_qualified_namespace(&self) -> &str {
+        &self.fully_qualified_namespace
+    }
+
+    /// The name of the queue
+    pub fn entity_path(&self) -> &str {
+        &self.queue
+    }
+
     /// Sends a message to the queue
-    pub async fn send_message(&self, msg: &str) -> Result<(), Error> {
+    ///
+    /// Anything convertible into a [`ServiceBusMessage`] can be sent directly,
+    /// e.g. `&str`, `String`, `&[u8]`, `Vec<u8>` or `bytes::Bytes`.
+    pub async fn send_message<M>(&self, msg: M) -> Result<(), Error>
+    where
+        M: Into<ServiceBusMessage>,
+    {
         send_message(
             &self.http_client,
             &self.namespace,
             &self.queue,
             &self.policy_name,
             &self.signing_key,
-            msg,
+            self.max_message_size_in_bytes,
+            &msg.into(),
+        )
+        .await
+    }
+
+    /// Sends a message to the queue with the given partition key
+    ///
+    /// This is a shorthand for setting the partition key with
+    /// [`ServiceBusMessage::set_partition_key`] before sending, and fails the
+    /// same way if the key does not match the session id of the message.
+    pub async fn send_message_with_partition_key<M>(
+        &self,
+        msg: M,
+        partition_key: &str,
+    ) -> Result<(), Error>
+    where
+        M: Into<ServiceBusMessage>,
+    {
+        let mut msg = msg.into();
+        msg.set_partition_key(partition_key)?;
+        self.send_message(msg).await
+    }
+
+    /// Sends a batch of messages to the queue in a single request
+    ///
+    /// The batch is only borrowed, so it can be sent again if the request fails.
+    pub async fn send_message_batch(&self, batch: &ServiceBusMessageBatch) -> Result<(), Error> {
+        send_message_batch(
+            &self.http_client,
+            &self.namespace,
+            &self.queue,
+            &self.policy_name,
+            &self.signing_key,
+            batch,
         )
         .await
     }
@@ -120,4 +239,389 @@ impl QueueClient {
         )
         .await
     }
+
+    /// Non-destructively read a message if one is available right away
+    ///
+    /// Unlike `peek_lock_message2`, this never waits for a message to arrive:
+    /// `None` is returned immediately if the queue is empty.
+    pub async fn try_peek_lock_message(&self) -> Result<Option<PeekLockResponse>, Error> {
+        self.peek_lock_message_with_timeout(Duration::ZERO).await
+    }
+
+    /// Non-destructively read a message, waiting up to `timeout` for one to arrive
+    ///
+    /// `None` is returned if the queue is still empty once the timeout has
+    /// elapsed. The service waits in whole seconds, so sub-second timeouts are
+    /// rounded down.
+    pub async fn peek_lock_message_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<PeekLockResponse>, Error> {
+        peek_lock_message_with_timeout(
+            &self.http_client,
+            &self.namespace,
+            &self.queue,
+            &self.policy_name,
+            &self.signing_key,
+            timeout,
+            None,
+        )
+        .await
+    }
+
+    /// Complete messages received from the queue with `peek_lock_message2`
+    ///
+    /// The outcome for each message is returned in the order of `messages`,
+    /// so a message whose lock was lost does not keep the others from being
+    /// completed. A few messages are completed at a time, one request each.
+    ///
+    /// Each message is settled through its own lock, so messages received
+    /// from another entity are completed too; the queue of this client is not
+    /// checked.
+    pub async fn complete_messages(&self, messages: &[PeekLockResponse]) -> Vec<Result<(), Error>> {
+        complete_messages(messages).await
+    }
+
+    /// Unlock messages received from the queue with `peek_lock_message2`
+    ///
+    /// The outcome for each message is returned in the order of `messages`.
+    /// Like [`complete_messages`](Self::complete_messages), each message is
+    /// unlocked through its own lock, whichever entity it was received from.
+    pub async fn abandon_messages(&self, messages: &[PeekLockResponse]) -> Vec<Result<(), Error>> {
+        abandon_messages(messages).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service_bus::{loopback::LoopbackHttpClient, ServiceBusError};
+
+    fn loopback_client() -> (Arc<LoopbackHttpClient>, QueueClient) {
+        let loopback = Arc::new(LoopbackHttpClient::default());
+        let client =
+            QueueClient::new(loopback.clone(), "loopback", "queue", "policy", "a2V5").unwrap();
+        (loopback, client)
+    }
+
+    #[tokio::test]
+    async fn sent_messages_are_received_in_order() {
+        let (_, client) = loopback_client();
+        client.send_message("first").await.unwrap();
+        client.send_message("second").await.unwrap();
+
+        assert_eq!(client.receive_and_delete_message().await.unwrap(), "first");
+        assert_eq!(client.peek_lock_message(None).await.unwrap(), "second");
+    }
+
+    #[tokio::test]
+    async fn batch_messages_keep_their_properties() {
+        let (_, client) = loopback_client();
+        let mut message = ServiceBusMessage::from("second");
+        message.set_message_id("message-2").unwrap();
+        message
+            .set_application_property("route", "eu-west")
+            .unwrap();
+        let mut batch = ServiceBusMessageBatch::new();
+        batch.try_add_message("first").unwrap();
+        batch.try_add_message(message).unwrap();
+        client.send_message_batch(&batch).await.unwrap();
+
+        let first = client.peek_lock_message2(None).await.unwrap();
+        let second = client.peek_lock_message2(None).await.unwrap();
+        assert_eq!(first.body(), "first");
+        assert_eq!(second.body(), "second");
+        assert_eq!(second.message_id(), Some("message-2"));
+        assert_eq!(
+            second
+                .application_properties()
+                .get("route")
+                .map(String::as_str),
+            Some("eu-west")
+        );
+        assert!(first.application_properties().is_empty());
+    }
+
+    #[tokio::test]
+    async fn oversized_message_is_rejected_before_sending() {
+        let (loopback, client) = loopback_client();
+        let body = vec![b'a'; 300 * 1024];
+
+        let error = client.send_message(body.clone()).await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::MessageSizeExceeded)
+        );
+        assert_eq!(loopback.message_count("queue"), 0);
+
+        let client = client.with_max_message_size_in_bytes(1024 * 1024);
+        client.send_message(body).await.unwrap();
+        assert_eq!(loopback.message_count("queue"), 1);
+    }
+
+    #[tokio::test]
+    async fn partition_key_is_stamped_on_sent_message() {
+        let (_, client) = loopback_client();
+        client
+            .send_message_with_partition_key("hello", "key-1")
+            .await
+            .unwrap();
+
+        let message = client.peek_lock_message2(None).await.unwrap();
+        assert_eq!(message.partition_key(), Some("key-1"));
+    }
+
+    #[tokio::test]
+    async fn session_id_is_received() {
+        let (_, client) = loopback_client();
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_session_id("session-1").unwrap();
+        client.send_message(message).await.unwrap();
+        client.send_message("no session").await.unwrap();
+
+        let message = client.peek_lock_message2(None).await.unwrap();
+        assert_eq!(message.session_id(), Some("session-1"));
+        let message = client.peek_lock_message2(None).await.unwrap();
+        assert_eq!(message.session_id(), None);
+    }
+
+    #[tokio::test]
+    async fn received_message_can_be_sent_again() {
+        let (loopback, client) = loopback_client();
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_message_id("message-1").unwrap();
+        message.set_session_id("session-1").unwrap();
+        message.set_subject("greeting");
+        message.set_content_type("text/plain");
+        message.set_time_to_live(Duration::from_secs(60));
+        message
+            .set_application_property("Route", "eu-west")
+            .unwrap();
+        client.send_message(message.clone()).await.unwrap();
+
+        let received = client.peek_lock_message2(None).await.unwrap();
+        let mut resent = ServiceBusMessage::from(&received);
+        assert_eq!(resent.time_to_live(), None);
+        resent.set_time_to_live(Duration::from_secs(60));
+        assert_eq!(resent, message);
+
+        client.send_message(resent).await.unwrap();
+        received.delete_message().await.unwrap();
+        assert_eq!(loopback.message_count("queue"), 1);
+    }
+
+    #[tokio::test]
+    async fn time_to_live_is_received() {
+        let (_, client) = loopback_client();
+        let mut message = ServiceBusMessage::from("hello");
+        message.set_time_to_live(Duration::from_secs(60));
+        client.send_message(message).await.unwrap();
+        client.send_message("forever").await.unwrap();
+
+        let message = client.peek_lock_message2(None).await.unwrap();
+        assert_eq!(message.time_to_live(), Some(Duration::from_secs(60)));
+        let expires_in = message.expires_at().unwrap() - time::OffsetDateTime::now_utc();
+        assert!(expires_in <= time::Duration::seconds(60));
+
+        let message = client.peek_lock_message2(None).await.unwrap();
+        assert!(message.expires_at().is_none());
+    }
+
+    #[tokio::test]
+    async fn completed_message_is_removed() {
+        let (loopback, client) = loopback_client();
+        client.send_message("hello").await.unwrap();
+
+        let mut message = client.peek_lock_message2(None).await.unwrap();
+        assert_eq!(loopback.message_count("queue"), 1);
+        message.renew_message_lock().await.unwrap();
+        message.delete_message().await.unwrap();
+        assert_eq!(loopback.message_count("queue"), 0);
+
+        let error = message.delete_message().await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::MessageLockExpired {
+                message_id: message.message_id().map(ToOwned::to_owned),
+                sequence_number: Some(1),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn renewed_lock_is_extended() {
+        let (_, client) = loopback_client();
+        client.send_message("hello").await.unwrap();
+
+        let mut message = client.peek_lock_message2(None).await.unwrap();
+        let locked_until = message.lock_expires_at().unwrap();
+        message.renew_message_lock().await.unwrap();
+
+        let renewed_until = message.lock_expires_at().unwrap();
+        assert!(renewed_until >= locked_until);
+        assert!(renewed_until <= time::OffsetDateTime::now_utc() + time::Duration::seconds(30));
+    }
+
+    #[tokio::test]
+    async fn clone_settles_the_same_message() {
+        let (loopback, client) = loopback_client();
+        client.send_message("hello").await.unwrap();
+
+        let message = client.peek_lock_message2(None).await.unwrap();
+        let clone = message.clone();
+        assert_eq!(clone.body(), "hello");
+        clone.delete_message().await.unwrap();
+        assert_eq!(loopback.message_count("queue"), 0);
+
+        let error = message.delete_message().await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ServiceBusError>(),
+            Some(ServiceBusError::MessageLockExpired { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn abandoned_message_is_redelivered() {
+        let (_, client) = loopback_client();
+        client.send_message("hello").await.unwrap();
+
+        let message = client.peek_lock_message2(None).await.unwrap();
+        assert_eq!(message.delivery_count(), Some(1));
+        assert_eq!(
+            message.state(),
+            Some(crate::service_bus::ServiceBusMessageState::Active)
+        );
+        message.unlock_message().await.unwrap();
+
+        let message = client.try_peek_lock_message().await.unwrap().unwrap();
+        assert_eq!(message.body(), "hello");
+        assert_eq!(message.delivery_count(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn messages_are_settled_individually() {
+        let (loopback, client) = loopback_client();
+        for body in ["first", "second", "third"] {
+            client.send_message(body).await.unwrap();
+        }
+
+        let mut messages = Vec::new();
+        for _ in 0..3 {
+            messages.push(client.peek_lock_message2(None).await.unwrap());
+        }
+        messages[1].delete_message().await.unwrap();
+
+        let results = client.complete_messages(&messages[..2]).await;
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1]
+                .as_ref()
+                .unwrap_err()
+                .downcast_ref::<ServiceBusError>(),
+            Some(ServiceBusError::MessageLockExpired { .. })
+        ));
+
+        let results = client.abandon_messages(&messages[2..]).await;
+        assert!(results[0].is_ok());
+        assert_eq!(loopback.message_count("queue"), 1);
+        let message = client.try_peek_lock_message().await.unwrap().unwrap();
+        assert_eq!(message.body(), "third");
+    }
+
+    #[tokio::test]
+    async fn many_messages_are_settled_in_order() {
+        let (loopback, client) = loopback_client();
+        let mut messages = Vec::new();
+        for i in 0..20 {
+            client.send_message(format!("message {i}")).await.unwrap();
+            messages.push(client.peek_lock_message2(None).await.unwrap());
+        }
+        messages[13].delete_message().await.unwrap();
+
+        let results = client.complete_messages(&messages).await;
+        assert_eq!(results.len(), 20);
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result.is_err(), i == 13, "message {i}");
+        }
+        assert_eq!(loopback.message_count("queue"), 0);
+    }
+
+    #[tokio::test]
+    async fn clones_send_concurrently() {
+        let (loopback, client) = loopback_client();
+
+        let tasks: Vec<_> = (0..2)
+            .map(|task| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    for i in 0..10 {
+                        client.send_message(format!("{task}-{i}")).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(loopback.message_count("queue"), 20);
+    }
+
+    #[tokio::test]
+    async fn peek_lock_with_timeout_returns_available_message() {
+        let (_, client) = loopback_client();
+        client.send_message("hello").await.unwrap();
+
+        let timeout = Duration::from_secs(1);
+        let message = client
+            .peek_lock_message_with_timeout(timeout)
+            .await
+            .unwrap();
+        assert_eq!(message.unwrap().body(), "hello");
+        assert!(client
+            .peek_lock_message_with_timeout(timeout)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn peek_lock_surfaces_service_bus_errors() {
+        for (status, expected) in [
+            (
+                azure_core::StatusCode::NotFound,
+                ServiceBusError::MessagingEntityNotFound,
+            ),
+            (
+                azure_core::StatusCode::ServiceUnavailable,
+                ServiceBusError::ServerBusy { retry_after: None },
+            ),
+        ] {
+            let (loopback, client) = loopback_client();
+            loopback.fail_with(status);
+
+            let error = client.peek_lock_message2(None).await.err().unwrap();
+            assert_eq!(error.downcast_ref::<ServiceBusError>(), Some(&expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn try_peek_lock_fails_on_error_status() {
+        let (loopback, client) = loopback_client();
+        client.send_message("hello").await.unwrap();
+        loopback.fail_with(azure_core::StatusCode::Unauthorized);
+
+        let error = client.try_peek_lock_message().await.err().unwrap();
+        assert_eq!(
+            error.downcast_ref::<ServiceBusError>(),
+            Some(&ServiceBusError::Unauthorized)
+        );
+    }
+
+    #[tokio::test]
+    async fn try_peek_lock_returns_none_when_empty() {
+        let (_, client) = loopback_client();
+
+        assert!(client.try_peek_lock_message().await.unwrap().is_none());
+    }
 }
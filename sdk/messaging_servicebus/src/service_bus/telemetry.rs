@@ -0,0 +1,74 @@
+use std::future::Future;
+
+/// Name of the counter incremented once per operation
+#[cfg(feature = "metrics")]
+const OPERATIONS_METRIC_NAME: &str = "servicebus_operations_total";
+
+/// Name of the histogram recording the duration of each operation in seconds
+#[cfg(feature = "metrics")]
+const OPERATION_DURATION_METRIC_NAME: &str = "servicebus_operation_duration_seconds";
+
+/// Path of the entity an operation targets, e.g. `mytopic/subscriptions/mysubscription`
+pub(crate) fn entity_path(queue_or_topic: &str, subscription: Option<&str>) -> String {
+    match subscription {
+        Some(subscription) => format!("{queue_or_topic}/subscriptions/{subscription}"),
+        None => queue_or_topic.to_owned(),
+    }
+}
+
+/// Runs `operation`, recording its outcome and duration with the `metrics` facade
+///
+/// Every operation increments the `servicebus_operations_total` counter and records
+/// into the `servicebus_operation_duration_seconds` histogram, labelled with the
+/// `entity`, the `operation` and its `outcome` (`success` or `failure`).
+#[cfg(feature = "metrics")]
+pub(crate) async fn record<F, T>(
+    entity_path: &str,
+    operation_name: &'static str,
+    operation: F,
+) -> azure_core::Result<T>
+where
+    F: Future<Output = azure_core::Result<T>>,
+{
+    let start = std::time::Instant::now();
+    let result = operation.await;
+    let elapsed = start.elapsed();
+
+    let outcome = if result.is_ok() { "success" } else { "failure" };
+    let labels = [
+        ("entity", entity_path.to_owned()),
+        ("operation", operation_name.to_owned()),
+        ("outcome", outcome.to_owned()),
+    ];
+    metrics::counter!(OPERATIONS_METRIC_NAME, 1, &labels);
+    metrics::histogram!(OPERATION_DURATION_METRIC_NAME, elapsed, &labels);
+
+    result
+}
+
+/// Runs `operation`; metrics are only recorded with the `metrics` feature
+#[cfg(not(feature = "metrics"))]
+pub(crate) async fn record<F, T>(
+    _entity_path: &str,
+    _operation_name: &'static str,
+    operation: F,
+) -> azure_core::Result<T>
+where
+    F: Future<Output = azure_core::Result<T>>,
+{
+    operation.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_path_includes_subscription() {
+        assert_eq!(entity_path("myqueue", None), "myqueue");
+        assert_eq!(
+            entity_path("mytopic", Some("mysubscription")),
+            "mytopic/subscriptions/mysubscription"
+        );
+    }
+}
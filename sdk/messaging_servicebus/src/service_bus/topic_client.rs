@@ -2,24 +2,36 @@ use std::sync::Arc;
 
 use crate::{
     service_bus::{
-        peek_lock_message, peek_lock_message2, receive_and_delete_message, send_message,
-        PeekLockResponse,
+        abandon_messages, complete_messages, custom_endpoint::CustomEndpointHttpClient,
+        message::DEFAULT_MAX_MESSAGE_SIZE_IN_BYTES, peek_lock_message, peek_lock_message2,
+        peek_lock_message_with_timeout, receive_and_delete_message, send_message,
+        send_message_batch, PeekLockResponse, ServiceBusConnectionStringProperties,
+        ServiceBusMessage, ServiceBusMessageBatch,
     },
-    utils::body_bytes_to_utf8,
+    utils::{body_bytes_to_utf8, dead_letter_queue_path, fully_qualified_namespace},
 };
 use ring::hmac::Key;
 use std::time::Duration;
+use url::Url;
 
 use azure_core::{error::Error, HttpClient};
 
 /// Client object that allows interaction with the `ServiceBus` API
+///
+/// Like [`QueueClient`](crate::service_bus::QueueClient), the client and the
+/// [`TopicSender`] and [`SubscriptionReceiver`] created from it are cheap to
+/// clone and share the same [`HttpClient`], so they can be handed to multiple tasks.
 #[derive(Debug, Clone)]
 pub struct TopicClient {
     http_client: Arc<dyn HttpClient>,
     namespace: String,
+    /// Host name of the namespace, kept for [`Self::fully_qualified_namespace`]
+    fully_qualified_namespace: String,
     topic: String,
     policy_name: String,
     signing_key: Key,
+    /// Largest message body sent, checked before the request is made
+    max_message_size_in_bytes: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -35,6 +47,10 @@ pub struct SubscriptionReceiver {
 
 impl TopicClient {
     /// Creates a new topic client instance
+    ///
+    /// All requests are sent through `http_client`, so any [`HttpClient`]
+    /// implementation can be used as the transport, e.g. an in-memory fake of
+    /// the service in tests.
     pub fn new<N, T, P, K>(
         http_client: Arc<dyn HttpClient>,
         namespace: N,
@@ -49,16 +65,77 @@ impl TopicClient {
         K: AsRef<str>,
     {
         let signing_key = Key::new(ring::hmac::HMAC_SHA256, policy_key.as_ref().as_bytes());
+        let namespace = namespace.into();
 
         Ok(Self {
             http_client,
-            namespace: namespace.into(),
+            fully_qualified_namespace: fully_qualified_namespace(&namespace),
+            namespace,
             topic: topic.into(),
             policy_name: policy_name.into(),
             signing_key,
+            max_message_size_in_bytes: DEFAULT_MAX_MESSAGE_SIZE_IN_BYTES,
         })
     }
 
+    /// Creates a new topic client instance from a connection string
+    ///
+    /// The topic is `topic` if given, otherwise the `EntityPath` of the connection
+    /// string. An error is returned if neither is present, if they differ, or if
+    /// the connection string does not contain a shared access key.
+    pub fn from_connection_string(
+        http_client: Arc<dyn HttpClient>,
+        connection_string: &str,
+        topic: Option<&str>,
+    ) -> Result<TopicClient, Error> {
+        let properties = ServiceBusConnectionStringProperties::parse(connection_string)?;
+        let credentials = properties.client_credentials(topic)?;
+
+        Self::new(
+            http_client,
+            credentials.namespace,
+            credentials.entity_path,
+            credentials.policy_name,
+            credentials.policy_key,
+        )
+    }
+
+    /// Sends the requests of this client to `custom_endpoint` instead of
+    /// `<namespace>.servicebus.windows.net`
+    ///
+    /// Use this to reach the namespace through a Private Link endpoint or a proxy.
+    /// Only the scheme, host and port of `custom_endpoint` are used; requests are
+    /// still authorized for the namespace.
+    pub fn with_custom_endpoint(mut self, custom_endpoint: Url) -> Result<Self, Error> {
+        self.http_client = Arc::new(CustomEndpointHttpClient::new(
+            self.http_client,
+            &self.namespace,
+            custom_endpoint,
+        )?);
+        Ok(self)
+    }
+
+    /// Lets this client send message bodies of up to `max_size_in_bytes`
+    ///
+    /// Messages larger than the 256 KB accepted by a Standard tier namespace
+    /// fail with
+    /// [`ServiceBusError::MessageSizeExceeded`](crate::service_bus::ServiceBusError::MessageSizeExceeded) before they are sent.
+    /// Use this to make use of the larger messages accepted by Premium tier namespaces.
+    pub fn with_max_message_size_in_bytes(mut self, max_size_in_bytes: usize) -> Self {
+        self.max_message_size_in_bytes = max_size_in_bytes;
+        self
+    }
+
+    /// The host name of the namespace, e.g. `<namespace>.servicebus.windows.net`
+    pub fn fully_qualified_namespace(&self) -> &str {
+        &self.fully_qualified_namespace
+    }
+
+    /// The name of the topic
+    pub fn entity_path(&self) -> &str {
+        &self.topic
+    }
+
     pub fn topic_sender(&self) -> TopicSender {
         TopicSender::new(self.clone())
     }
@@ -66,21 +143,79 @@ impl TopicClient {
     pub fn subscription_receiver(&self, subscription: &str) -> SubscriptionReceiver {
         SubscriptionReceiver::new(self.clone(), subscription)
     }
+
+    /// Creates a receiver for the dead-letter queue of a subscription
+    ///
+    /// Messages are received from `<topic>/subscriptions/<subscription>/$DeadLetterQueue`.
+    pub fn subscription_dead_letter_receiver(&self, subscription: &str) -> SubscriptionReceiver {
+        SubscriptionReceiver::new(self.clone(), dead_letter_queue_path(subscription))
+    }
 }
 
 impl TopicSender {
     pub fn new(topic_client: TopicClient) -> TopicSender {
         Self { topic_client }
     }
+
+    /// The host name of the namespace, e.g. `<namespace>.servicebus.windows.net`
+    pub fn fully_qualified_namespace(&self) -> &str {
+        self.topic_client.fully_qualified_namespace()
+    }
+
+    /// The name of the topic
+    pub fn entity_path(&self) -> &str {
+        self.topic_client.entity_path()
+    }
+
     /// Sends a message to the topic
-    pub async fn send_message(&self, msg: &str) -> Result<(), Error> {
+    ///
+    /// Anything convertible into a [`ServiceBusMessage`] can be sent directly,
+    /// e.g. `&str`, `String`, `&[u8]`, `Vec<u8>` or `bytes::Bytes`.
+    pub async fn send_message<M>(&self, msg: M) -> Result<(), Error>
+    where
+        M: Into<ServiceBusMessage>,
+    {
         send_message(
             &self.topic_client.http_client,
             &self.topic_client.namespace,
             &self.topic_client.topic,
             &self.topic_client.policy_name,
             &self.topic_client.signing_key,
-            msg,
+            self.topic_client.max_message_size_in_bytes,
+            &msg.into(),
+        )
+        .await
+    }
+
+    /// Sends a message to the topic with the given partition key
+    ///
+    /// This is a shorthand for setting the partition key with
+    /// [`ServiceBusMessage::set_partition_key`] before sending, and fails the
+    /// same way if the key does not match the session id of the message.
+    pub async fn send_message_with_partition_key<M>(
+        &self,
+        msg: M,
+        partition_key: &str,
+    ) -> Result<(), Error>
+    where
+        M: Into<ServiceBusMessage>,
+    {
+        let mut msg = msg.into();
+        msg.set_partition_key(partition_key)?;
+        self.send_message(msg).await
+    }
+
+    /// Sends a batch of messages to the topic in a single request
+    ///
+    /// The batch is only borrowed, so it can be sent again if the request fails.
+    pub async fn send_message_batch(&self, batch: &ServiceBusMessageBatch) -> Result<(), Error> {
+        send_message_batch(
+            &self.topic_client.http_client,
+            &self.topic_client.namespace,
+            &self.topic_client.topic,
+            &self.topic_client.policy_name,
+            &self.topic_client.signing_key,
+            batch,
         )
         .await
     }
@@ -156,4 +291,170 @@ impl SubscriptionReceiver {
         )
         .await
     }
+
+    /// Non-destructively read a message if one is available right away
+    ///
+    /// Unlike `peek_lock_message2`, this never waits for a message to arrive:
+    /// `None` is returned immediately if the subscription is empty.
+    pub async fn try_peek_lock_message(&self) -> Result<Option<PeekLockResponse>, Error> {
+        self.peek_lock_message_with_timeout(Duration::ZERO).await
+    }
+
+    /// Non-destructively read a message, waiting up to `timeout` for one to arrive
+    ///
+    /// `None` is returned if the subscription is still empty once the timeout has
+    /// elapsed. The service waits in whole seconds, so sub-second timeouts are
+    /// rounded down.
+    pub async fn peek_lock_message_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<PeekLockResponse>, Error> {
+        peek_lock_message_with_timeout(
+            &self.topic_client.http_client,
+            &self.topic_client.namespace,
+            &self.topic_client.topic,
+            &self.topic_client.policy_name,
+            &self.topic_client.signing_key,
+            timeout,
+            Some(&self.subscription),
+        )
+        .await
+    }
+
+    /// Complete messages received from the subscription with `peek_lock_message2`
+    ///
+    /// The outcome for each message is returned in the order of `messages`,
+    /// so a message whose lock was lost does not keep the others from being
+    /// completed. A few messages are completed at a time, one request each.
+    ///
+    /// Each message is settled through its own lock, so messages received
+    /// from another entity are completed too; the subscription of this client is not
+    /// checked.
+    pub async fn complete_messages(&self, messages: &[PeekLockResponse]) -> Vec<Result<(), Error>> {
+        complete_messages(messages).await
+    }
+
+    /// Unlock messages received from the subscription with `peek_lock_message2`
+    ///
+    /// The outcome for each message is returned in the order of `messages`.
+    /// Like [`complete_messages`](Self::complete_messages), each message is
+    /// unlocked through its own lock, whichever entity it was received from.
+    pub async fn abandon_messages(&self, messages: &[PeekLockResponse]) -> Vec<Result<(), Error>> {
+        abandon_messages(messages).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service_bus::loopback::LoopbackHttpClient;
+
+    #[test]
+    fn sender_exposes_namespace_and_topic() {
+        let client = TopicClient::new(
+            azure_core::new_http_client(),
+            "namespace",
+            "topic",
+            "policy",
+            "a2V5",
+        )
+        .unwrap();
+        let sender = client.topic_sender();
+
+        assert_eq!(
+            sender.fully_qualified_namespace(),
+            "namespace.servicebus.windows.net"
+        );
+        assert_eq!(sender.entity_path(), "topic");
+    }
+
+    #[tokio::test]
+    async fn dead_letter_receiver_targets_dead_letter_queue() {
+        let loopback = Arc::new(LoopbackHttpClient::default());
+        let client =
+            TopicClient::new(loopback.clone(), "loopback", "topic", "policy", "a2V5").unwrap();
+        // stands in for the service moving a message to the dead-letter queue
+        crate::service_bus::QueueClient::new(
+            loopback.clone(),
+            "loopback",
+            "topic/subscriptions/subscription/$DeadLetterQueue",
+            "policy",
+            "a2V5",
+        )
+        .unwrap()
+        .send_message("dead-lettered")
+        .await
+        .unwrap();
+
+        let receiver = client.subscription_receiver("subscription");
+        assert!(receiver.try_peek_lock_message().await.unwrap().is_none());
+
+        let dead_letter_receiver = client.subscription_dead_letter_receiver("subscription");
+        let message = dead_letter_receiver.peek_lock_message2(None).await.unwrap();
+        assert_eq!(message.body(), "dead-lettered");
+        message.delete_message().await.unwrap();
+        assert_eq!(
+            loopback.message_count("topic/subscriptions/subscription/$DeadLetterQueue"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn subscription_receives_topic_messages() {
+        let loopback = Arc::new(LoopbackHttpClient::default());
+        let client =
+            TopicClient::new(loopback.clone(), "loopback", "topic", "policy", "a2V5").unwrap();
+        client.topic_sender().send_message("hello").await.unwrap();
+
+        let receiver = client.subscription_receiver("subscription");
+        let message = receiver.peek_lock_message2(None).await.unwrap();
+        assert_eq!(message.body(), "hello");
+        message.delete_message().await.unwrap();
+
+        assert_eq!(
+            loopback.message_count("topic/subscriptions/subscription"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn every_subscription_receives_topic_messages() {
+        let loopback = Arc::new(LoopbackHttpClient::default());
+        let client =
+            TopicClient::new(loopback.clone(), "loopback", "topic", "policy", "a2V5").unwrap();
+        let audit = client.subscription_receiver("audit");
+        assert!(audit.try_peek_lock_message().await.unwrap().is_none());
+
+        client.topic_sender().send_message("hello").await.unwrap();
+        let billing = client.subscription_receiver("billing");
+        assert_eq!(loopback.message_count("topic/subscriptions/billing"), 1);
+
+        for receiver in [&audit, &billing] {
+            let message = receiver.peek_lock_message2(None).await.unwrap();
+            assert_eq!(message.body(), "hello");
+            message.delete_message().await.unwrap();
+        }
+        assert_eq!(loopback.message_count("topic/subscriptions/audit"), 0);
+        assert_eq!(loopback.message_count("topic/subscriptions/billing"), 0);
+    }
+
+    #[tokio::test]
+    async fn peek_lock_with_timeout_fails_on_missing_subscription() {
+        let loopback = Arc::new(LoopbackHttpClient::default());
+        let client =
+            TopicClient::new(loopback.clone(), "loopback", "topic", "policy", "a2V5").unwrap();
+        client.topic_sender().send_message("hello").await.unwrap();
+        loopback.fail_with(azure_core::StatusCode::NotFound);
+
+        let error = client
+            .subscription_receiver("subscription")
+            .peek_lock_message_with_timeout(Duration::from_secs(1))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.downcast_ref::<crate::service_bus::ServiceBusError>(),
+            Some(&crate::service_bus::ServiceBusError::MessagingEntityNotFound)
+        );
+    }
 }
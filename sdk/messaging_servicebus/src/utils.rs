@@ -2,6 +2,9 @@ use azure_core::error::{Error, ErrorKind, ResultExt};
 use std::time::Duration;
 use url::Url;
 
+/// Name of the sub-queue holding the dead-lettered messages of a queue or subscription
+const DEAD_LETTER_QUEUE_NAME: &str = "$DeadLetterQueue";
+
 pub fn craft_peek_lock_url(
     namespace: &str,
     queue_or_topic: &str,
@@ -33,12 +36,109 @@ pub fn body_bytes_to_utf8(bytes: &[u8]) -> Result<String, Error> {
 }
 
 pub fn get_head_url(namespace: &str, queue_or_topic: &str, subscription: Option<&str>) -> String {
-    match subscription {
-        Some(sub) => format!(
-            "https://{namespace}.servicebus.windows.net/{queue_or_topic}/subscriptions/{sub}/messages/head"
-        ),
-        None => format!(
-            "https://{namespace}.servicebus.windows.net/{queue_or_topic}/messages/head"
-        ),
+    entity_url_string(
+        namespace,
+        queue_or_topic,
+        subscription,
+        &["messages", "head"],
+    )
+}
+
+/// Host name of a namespace, e.g. `<namespace>.servicebus.windows.net`
+pub fn fully_qualified_namespace(namespace: &str) -> String {
+    format!("{namespace}.servicebus.windows.net")
+}
+
+/// URL of `path` below a queue, topic or subscription
+///
+/// Entity names are split on `/`, which separates the levels of hierarchical
+/// names and sub-queues like `$DeadLetterQueue`, and each segment is
+/// percent-encoded, so names with reserved characters such as spaces, `#` or
+/// `?` still address the right entity.
+pub fn entity_url(
+    namespace: &str,
+    queue_or_topic: &str,
+    subscription: Option<&str>,
+    path: &[&str],
+) -> Result<Url, Error> {
+    let url = entity_url_string(namespace, queue_or_topic, subscription, path);
+    Url::parse(&url).with_context(ErrorKind::DataConversion, || {
+        format!("failed to parse entity URL: {url}")
+    })
+}
+
+fn entity_url_string(
+    namespace: &str,
+    queue_or_topic: &str,
+    subscription: Option<&str>,
+    path: &[&str],
+) -> String {
+    let mut url = format!("https://{}", fully_qualified_namespace(namespace));
+    for segment in queue_or_topic.split('/') {
+        push_path_segment(&mut url, segment);
+    }
+    if let Some(subscription) = subscription {
+        push_path_segment(&mut url, "subscriptions");
+        for segment in subscription.split('/') {
+            push_path_segment(&mut url, segment);
+        }
+    }
+    for segment in path {
+        push_path_segment(&mut url, segment);
+    }
+    url
+}
+
+/// Appends `segment` to `url`, percent-encoding the bytes not allowed in a
+/// path segment, as well as `/` and `%` so the segment is kept whole
+fn push_path_segment(url: &mut String, segment: &str) {
+    url.push('/');
+    for byte in segment.bytes() {
+        match byte {
+            b' '
+            | b'"'
+            | b'#'
+            | b'%'
+            | b'/'
+            | b'<'
+            | b'>'
+            | b'?'
+            | b'`'
+            | b'{'
+            | b'}'
+            | 0..=0x1f
+            | 0x7f.. => url.push_str(&format!("%{byte:02X}")),
+            _ => url.push(char::from(byte)),
+        }
+    }
+}
+
+/// Path of the dead-letter queue of a queue or subscription, relative to its parent
+pub fn dead_letter_queue_path(queue_or_subscription: &str) -> String {
+    format!("{queue_or_subscription}/{DEAD_LETTER_QUEUE_NAME}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_names_are_percent_encoded() {
+        let url = entity_url("namespace", "my queue", None, &["messages"]).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://namespace.servicebus.windows.net/my%20queue/messages"
+        );
+
+        assert_eq!(
+            get_head_url("namespace", "orders/v1.0", Some("audit #1")),
+            "https://namespace.servicebus.windows.net/orders/v1.0/subscriptions/audit%20%231/messages/head"
+        );
+
+        let url = entity_url("namespace", "naïve 100%", None, &["messages"]).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://namespace.servicebus.windows.net/na%C3%AFve%20100%25/messages"
+        );
     }
 }
@@ -1,8 +1,10 @@
 #![cfg(all(test, feature = "test_e2e"))] // to run this, do: `cargo test --features test_e2e`
 extern crate log;
 
-use azure_messaging_servicebus::service_bus::QueueClient;
+use azure_core::StatusCode;
+use azure_messaging_servicebus::service_bus::{QueueClient, ServiceBusMessage};
 use std::time::Duration;
+use time::OffsetDateTime;
 
 #[tokio::test]
 async fn send_message_test() {
@@ -82,13 +84,120 @@ async fn unlock_message() {
         .expect("Failed to unlock message's lock");
 }
 
-fn create_client() -> azure_core::Result<QueueClient> {
-    let service_bus_namespace = std::env::var("AZURE_SERVICE_BUS_NAMESPACE")
-        .expect("Please set AZURE_SERVICE_BUS_NAMESPACE env variable first!");
+#[tokio::test]
+async fn try_peek_lock_message_test() {
+    let client = create_client().unwrap();
+    client
+        .send_message("hello, world!")
+        .await
+        .expect("Failed to send message");
+    client
+        .try_peek_lock_message()
+        .await
+        .expect("Failed to receive message")
+        .expect("No message available")
+        .delete_message()
+        .await
+        .expect("Failed to delete message");
+}
+
+#[tokio::test]
+async fn expired_message_is_not_received_test() {
+    let client = create_expiry_client().unwrap();
+    let message_id = format!(
+        "expiring-{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos()
+    );
+
+    let mut message = ServiceBusMessage::from("hello, expiry!");
+    message
+        .set_message_id(message_id.clone())
+        .expect("Failed to set message id");
+    message.set_time_to_live(Duration::from_secs(5));
+    client
+        .send_message(message)
+        .await
+        .expect("Failed to send message");
+
+    tokio::time::sleep(Duration::from_secs(10)).await;
+
+    while let Some(received) = client
+        .try_peek_lock_message()
+        .await
+        .expect("Failed to receive message")
+    {
+        received
+            .delete_message()
+            .await
+            .expect("Failed to delete message");
+        assert_ne!(
+            received.message_id(),
+            Some(message_id.as_str()),
+            "the expired message was received"
+        );
+    }
+}
+
+#[tokio::test]
+async fn duplicate_message_id_is_dropped_test() {
+    let client = create_dedup_client().unwrap();
+    let message_id = format!("dedup-{}", OffsetDateTime::now_utc().unix_timestamp_nanos());
+
+    for _ in 0..2 {
+        let mut message = ServiceBusMessage::from("hello, dedup!");
+        message
+            .set_message_id(message_id.clone())
+            .expect("Failed to set message id");
+        client
+            .send_message(message)
+            .await
+            .expect("Failed to send message");
+    }
+
+    let received = client
+        .peek_lock_message2(Some(Duration::from_secs(5)))
+        .await
+        .expect("Failed to receive message");
+    assert_eq!(received.message_id(), Some(message_id.as_str()));
+    received
+        .delete_message()
+        .await
+        .expect("Failed to delete message");
+
+    let duplicate = client
+        .peek_lock_message2(Some(Duration::from_secs(5)))
+        .await
+        .expect("Failed to receive message");
+    assert_eq!(*duplicate.status(), StatusCode::NoContent);
+}
 
+fn create_client() -> azure_core::Result<QueueClient> {
     let queue_name =
         std::env::var("AZURE_QUEUE_NAME").expect("Please set AZURE_QUEUE_NAME env variable first!");
 
+    create_client_for_queue(queue_name)
+}
+
+/// Creates a client for a queue with duplicate detection enabled
+fn create_dedup_client() -> azure_core::Result<QueueClient> {
+    let queue_name = std::env::var("AZURE_DEDUP_QUEUE_NAME")
+        .expect("Please set AZURE_DEDUP_QUEUE_NAME env variable first!");
+
+    create_client_for_queue(queue_name)
+}
+
+/// Creates a client for a queue used only by the expiry test, which drains it
+fn create_expiry_client() -> azure_core::Result<QueueClient> {
+    let queue_name = std::env::var("AZURE_EXPIRY_QUEUE_NAME")
+        .expect("Please set AZURE_EXPIRY_QUEUE_NAME env variable first!");
+
+    create_client_for_queue(queue_name)
+}
+
+fn create_client_for_queue(queue_name: String) -> azure_core::Result<QueueClient> {
+    let service_bus_namespace = std::env::var("AZURE_SERVICE_BUS_NAMESPACE")
+        .expect("Please set AZURE_SERVICE_BUS_NAMESPACE env variable first!");
+
     let policy_name = std::env::var("AZURE_POLICY_NAME")
         .expect("Please set AZURE_POLICY_NAME env variable first!");
 
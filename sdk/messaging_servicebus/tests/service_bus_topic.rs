@@ -0,0 +1,103 @@
+#![cfg(all(test, feature = "test_e2e"))] // to run this, do: `cargo test --features test_e2e`
+
+use azure_messaging_servicebus::service_bus::{SubscriptionReceiver, TopicClient};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+#[tokio::test]
+async fn send_message_to_topic_test() {
+    let client = create_client().unwrap();
+    client
+        .topic_sender()
+        .send_message("hello, topic!")
+        .await
+        .expect("Failed to send message");
+}
+
+#[tokio::test]
+async fn every_subscription_receives_sent_message_test() {
+    let client = create_client().unwrap();
+    let body = format!(
+        "fan-out-{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos()
+    );
+
+    client
+        .topic_sender()
+        .send_message(body.as_str())
+        .await
+        .expect("Failed to send message");
+
+    for subscription in subscription_names() {
+        let receiver = client.subscription_receiver(&subscription);
+        assert!(
+            receive_body(&receiver, &body).await,
+            "subscription {subscription} did not receive the message"
+        );
+    }
+}
+
+/// Receives from the subscription until the message with `body` is found,
+/// completing every message received on the way
+async fn receive_body(receiver: &SubscriptionReceiver, body: &str) -> bool {
+    while let Some(message) = receiver
+        .try_peek_lock_message()
+        .await
+        .expect("Failed to receive message")
+    {
+        message
+            .delete_message()
+            .await
+            .expect("Failed to delete message");
+        if message.body() == body {
+            return true;
+        }
+    }
+
+    // give a message still in flight to the subscription a chance to arrive
+    let message = receiver
+        .peek_lock_message2(Some(Duration::from_secs(5)))
+        .await
+        .expect("Failed to receive message");
+    if message.body() != body {
+        return false;
+    }
+    message
+        .delete_message()
+        .await
+        .expect("Failed to delete message");
+    true
+}
+
+fn subscription_names() -> [String; 2] {
+    [
+        std::env::var("AZURE_SUBSCRIPTION_NAME")
+            .expect("Please set AZURE_SUBSCRIPTION_NAME env variable first!"),
+        std::env::var("AZURE_SECOND_SUBSCRIPTION_NAME")
+            .expect("Please set AZURE_SECOND_SUBSCRIPTION_NAME env variable first!"),
+    ]
+}
+
+fn create_client() -> azure_core::Result<TopicClient> {
+    let service_bus_namespace = std::env::var("AZURE_SERVICE_BUS_NAMESPACE")
+        .expect("Please set AZURE_SERVICE_BUS_NAMESPACE env variable first!");
+
+    let topic_name =
+        std::env::var("AZURE_TOPIC_NAME").expect("Please set AZURE_TOPIC_NAME env variable first!");
+
+    let policy_name = std::env::var("AZURE_POLICY_NAME")
+        .expect("Please set AZURE_POLICY_NAME env variable first!");
+
+    let policy_key =
+        std::env::var("AZURE_POLICY_KEY").expect("Please set AZURE_POLICY_KEY env variable first!");
+
+    let http_client = azure_core::new_http_client();
+
+    TopicClient::new(
+        http_client,
+        service_bus_namespace,
+        topic_name,
+        policy_name,
+        policy_key,
+    )
+}